@@ -117,8 +117,7 @@ mod tests {
 
         // invert color of to_image
         (0..height)
-            .into_iter()
-            .flat_map(|y| (0..width).into_iter().map(move |x| (x, y)))
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
             .for_each(|(x, y)| {
                 assert_eq!(from_image[(x, y)], to_image[(x, y)]);
             });
@@ -132,10 +131,9 @@ mod tests {
 
         let flat_from = {
             let mut samples = Vec::<u8>::new();
-            (0..height).into_iter().for_each(|y| {
-                let mut row = vec![];
-                row.resize(stride, 0);
-                (0..width).into_iter().for_each(|x| {
+            (0..height).for_each(|y| {
+                let mut row = vec![0; stride];
+                (0..width).for_each(|x| {
                     if x == y {
                         row[x] = 255;
                     }
@@ -160,8 +158,7 @@ mod tests {
 
         let image = Image::from_flat_samples(&flat_from);
         (0..height)
-            .into_iter()
-            .flat_map(|y| (0..width).into_iter().map(move |x| (x, y)))
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
             .for_each(|(x, y)| {
                 if x == y {
                     assert_eq!(image[(x, y)], 255);
@@ -189,7 +186,7 @@ mod tests {
         let height = 80;
         let image_buf_from = {
             let mut buf = ImageBuffer::<Luma<u8>, _>::new(width, height);
-            (0..(width.min(height))).into_iter().for_each(|idx| {
+            (0..(width.min(height))).for_each(|idx| {
                 buf[(idx, idx)][0] = 255;
             });
             buf
@@ -197,8 +194,7 @@ mod tests {
 
         let image = Image::from_image_buffer(&image_buf_from);
         (0..height)
-            .into_iter()
-            .flat_map(|y| (0..width).into_iter().map(move |x| (x, y)))
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
             .for_each(|(x, y)| {
                 if x == y {
                     assert_eq!(image[(x as usize, y as usize)], 255);
@@ -220,7 +216,7 @@ mod tests {
 
     fn diagonal_image(width: usize, height: usize) -> Image {
         let mut image = Image::zeros_with_alignment(width, height, DEFAULT_ALIGNMENT_U8).unwrap();
-        (0..width.min(height)).into_iter().for_each(|index| {
+        (0..width.min(height)).for_each(|index| {
             image[(index, index)] = 255;
         });
         image
@@ -14,21 +14,45 @@ struct Opts {
     /// optional tag parameters in format "tagsize,fx,fy,cx,cy".
     pub tag_params: Option<TagParamsArg>,
 
+    #[cfg(feature = "calibration")]
+    #[clap(long, requires = "tag_size", conflicts_with = "tag_params")]
+    /// optional OpenCV YAML, ROS camera_info or JSON calibration file.
+    pub calibration: Option<String>,
+
+    #[cfg(feature = "calibration")]
+    #[clap(long)]
+    /// the tag size used with the calibration file.
+    pub tag_size: Option<f64>,
+
     /// a list of input PNM image files.
     pub input_files: Vec<String>,
 }
 
 fn main() -> Result<()> {
+    let opts = Opts::parse();
+    #[cfg(feature = "calibration")]
+    let calibration_params = match (&opts.calibration, opts.tag_size) {
+        (Some(path), Some(tag_size)) => {
+            let calibration = apriltag::calibration::CameraCalibration::from_file(path)?;
+            Some(calibration.tag_params(tag_size))
+        }
+        _ => None,
+    };
+    #[cfg(not(feature = "calibration"))]
+    let calibration_params = None;
     let Opts {
         family_name,
         tag_params,
         input_files,
-    } = Opts::parse();
+        ..
+    } = opts;
 
     ensure!(!input_files.is_empty(), "no input files");
 
     let family: Family = family_name.parse()?;
-    let tag_params: Option<TagParams> = tag_params.map(|params| params.into());
+    let tag_params: Option<TagParams> = tag_params
+        .map(|params| params.into())
+        .or(calibration_params);
     let mut detector = Detector::builder().add_family_bits(family, 1).build()?;
 
     for path in input_files {
@@ -0,0 +1,63 @@
+use anyhow::{bail, Context, Result};
+use apriltag::{FamilyGenerator, Layout};
+use clap::Parser;
+use std::fs;
+
+/// Generate a tag family and write it as C source.
+#[derive(Debug, Clone, Parser)]
+struct Opts {
+    #[clap(long, default_value = "classic:6")]
+    /// the tag layout as "classic:<border width>", "standard:<total width>" or a layout file.
+    pub layout: String,
+
+    #[clap(long)]
+    /// the minimum Hamming distance between codes.
+    pub min_hamming: usize,
+
+    #[clap(long, default_value = "0")]
+    /// the minimum number of black rectangles needed to draw a tag.
+    pub min_complexity: usize,
+
+    #[clap(long)]
+    /// stop after finding this many codes.
+    pub max_codes: Option<usize>,
+
+    #[clap(long)]
+    /// the family name, "tagCustom<bits>h<hamming>" by default.
+    pub name: Option<String>,
+
+    /// the output C source file.
+    pub output: String,
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+    let layout = match opts.layout.split_once(':') {
+        Some(("classic", width)) => Layout::classic(width.parse()?)?,
+        Some(("standard", width)) => Layout::standard(width.parse()?)?,
+        Some(_) => bail!("unknown layout '{}'", opts.layout),
+        None => {
+            let text = fs::read_to_string(&opts.layout)
+                .with_context(|| format!("failed to read layout {}", opts.layout))?;
+            Layout::parse(&text)?
+        }
+    };
+
+    let name = opts
+        .name
+        .unwrap_or_else(|| format!("tagCustom{}h{}", layout.nbits(), opts.min_hamming));
+    let mut generator =
+        FamilyGenerator::new(layout, opts.min_hamming).min_complexity(opts.min_complexity);
+    if let Some(max_codes) = opts.max_codes {
+        generator = generator.max_codes(max_codes);
+    }
+    let family = generator.generate(&name)?;
+
+    fs::write(&opts.output, family.to_c_source())?;
+    println!(
+        "wrote {} codes of {name} to {}",
+        family.codes.len(),
+        opts.output
+    );
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Loaders for camera calibration files.
+//!
+//! It reads the intrinsics and distortion coefficients from OpenCV
+//! `cv::FileStorage` YAML, ROS `camera_info` YAML and the JSON documents
+//! written by the focal length estimator, so that pose estimation and
+//! calibration tools share one source of truth.
+//!
+//! ```no_run
+//! use apriltag::calibration::CameraCalibration;
+//! let calibration = CameraCalibration::from_file("camera.yaml").unwrap();
+//! let tag_params = calibration.tag_params(0.1);
+//! ```
+
+use crate::{error::Error, pose::TagParams};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{fs, path::Path};
+
+/// Lens distortion coefficients of a calibration file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distortion {
+    /// No distortion.
+    None,
+    /// Brown-Conrady coefficients, called `plumb_bob` in ROS.
+    BrownConrady {
+        k1: f64,
+        k2: f64,
+        k3: f64,
+        p1: f64,
+        p2: f64,
+    },
+    /// Kannala-Brandt fisheye coefficients, called `equidistant` in ROS.
+    KannalaBrandt { k1: f64, k2: f64, k3: f64, k4: f64 },
+}
+
+/// Camera intrinsics and distortion loaded from a calibration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraCalibration {
+    pub image_width: Option<usize>,
+    pub image_height: Option<usize>,
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub skew: f64,
+    pub distortion: Distortion,
+}
+
+impl CameraCalibration {
+    /// Loads a calibration file in any of the supported formats.
+    ///
+    /// Files starting with `{` are read as JSON, files with the `%YAML:1.0`
+    /// directive or `!!opencv-matrix` tags as OpenCV YAML, and other files as
+    /// ROS `camera_info` YAML.
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| Error::LoadCalibrationError {
+            reason: format!("failed to read '{}': {error}", path.display()),
+        })?;
+        let trimmed = text.trim_start();
+
+        if trimmed.starts_with('{') {
+            Self::from_json(&text)
+        } else if trimmed.starts_with("%YAML:1.0") || text.contains("!!opencv-matrix") {
+            Self::from_opencv_yaml(&text)
+        } else {
+            Self::from_ros_camera_info(&text)
+        }
+    }
+
+    /// Parses a JSON document written by the focal length estimator.
+    pub fn from_json(text: &str) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct Json {
+            image_width: Option<usize>,
+            image_height: Option<usize>,
+            model: String,
+            fx: f64,
+            fy: f64,
+            cx: f64,
+            cy: f64,
+            #[serde(default)]
+            skew: f64,
+            #[serde(default)]
+            distortion: Vec<f64>,
+        }
+
+        let json: Json =
+            serde_json::from_str(text).map_err(|error| Error::LoadCalibrationError {
+                reason: format!("invalid calibration JSON: {error}"),
+            })?;
+        let distortion = match json.model.as_str() {
+            "pinhole" => Distortion::None,
+            "brown-conrady" => Distortion::from_opencv(&json.distortion)?,
+            "kannala-brandt" => Distortion::from_fisheye(&json.distortion)?,
+            model => {
+                return Err(Error::LoadCalibrationError {
+                    reason: format!("unknown camera model '{model}'"),
+                })
+            }
+        };
+
+        Ok(Self {
+            image_width: json.image_width,
+            image_height: json.image_height,
+            fx: json.fx,
+            fy: json.fy,
+            cx: json.cx,
+            cy: json.cy,
+            skew: json.skew,
+            distortion,
+        })
+    }
+
+    /// Parses an OpenCV `cv::FileStorage` YAML file with `camera_matrix` and
+    /// `distortion_coefficients` entries.
+    ///
+    /// The optional `distortion_model` entry selects the fisheye coefficients
+    /// when set to `equidistant` or `fisheye`.
+    pub fn from_opencv_yaml(text: &str) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct OpenCvYaml {
+            image_width: Option<usize>,
+            image_height: Option<usize>,
+            camera_matrix: Matrix,
+            distortion_model: Option<String>,
+            distortion_coefficients: Option<Matrix>,
+        }
+
+        // serde_yaml understands neither the YAML 1.0 directive nor OpenCV's tags
+        let text: String = text
+            .lines()
+            .filter(|line| !line.starts_with("%YAML"))
+            .map(|line| line.replace("!!opencv-matrix", "") + "\n")
+            .collect();
+        let yaml: OpenCvYaml = parse_yaml(&text)?;
+        let coefficients = yaml
+            .distortion_coefficients
+            .map(|matrix| matrix.data)
+            .unwrap_or_default();
+
+        Self::from_parts(
+            yaml.image_width,
+            yaml.image_height,
+            &yaml.camera_matrix,
+            yaml.distortion_model.as_deref().unwrap_or("plumb_bob"),
+            &coefficients,
+        )
+    }
+
+    /// Parses a ROS `sensor_msgs/CameraInfo` YAML file, as written by
+    /// `camera_calibration` and read by `camera_info_manager`.
+    pub fn from_ros_camera_info(text: &str) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct RosYaml {
+            image_width: Option<usize>,
+            image_height: Option<usize>,
+            camera_matrix: Matrix,
+            distortion_model: String,
+            distortion_coefficients: Matrix,
+        }
+
+        let yaml: RosYaml = parse_yaml(text)?;
+        Self::from_parts(
+            yaml.image_width,
+            yaml.image_height,
+            &yaml.camera_matrix,
+            &yaml.distortion_model,
+            &yaml.distortion_coefficients.data,
+        )
+    }
+
+    /// Builds the pose estimation parameters for a tag of the given size, in
+    /// the sense of [TagParams::tagsize].
+    ///
+    /// The parameters describe an ideal pinhole camera, so the tag corners must
+    /// be undistorted beforehand, e.g. with [Detection::with_corners](crate::Detection::with_corners).
+    pub fn tag_params(&self, tagsize: f64) -> TagParams {
+        TagParams {
+            tagsize,
+            fx: self.fx,
+            fy: self.fy,
+            cx: self.cx,
+            cy: self.cy,
+        }
+    }
+
+    fn from_parts(
+        image_width: Option<usize>,
+        image_height: Option<usize>,
+        camera_matrix: &Matrix,
+        distortion_model: &str,
+        coefficients: &[f64],
+    ) -> Result<Self, Error> {
+        let &[fx, skew, cx, _, fy, cy, _, _, _] = camera_matrix.data.as_slice() else {
+            return Err(Error::LoadCalibrationError {
+                reason: format!(
+                    "the camera matrix must have 9 values, but got {}",
+                    camera_matrix.data.len()
+                ),
+            });
+        };
+        let distortion = match distortion_model {
+            "plumb_bob" => Distortion::from_opencv(coefficients)?,
+            "equidistant" | "fisheye" => Distortion::from_fisheye(coefficients)?,
+            model => {
+                return Err(Error::LoadCalibrationError {
+                    reason: format!("unsupported distortion model '{model}'"),
+                })
+            }
+        };
+
+        Ok(Self {
+            image_width,
+            image_height,
+            fx,
+            fy,
+            cx,
+            cy,
+            skew,
+            distortion,
+        })
+    }
+}
+
+impl Distortion {
+    /// Reads coefficients in OpenCV order `k1, k2, p1, p2[, k3]`.
+    ///
+    /// All-zero coefficients mean no distortion.
+    fn from_opencv(coefficients: &[f64]) -> Result<Self, Error> {
+        if coefficients.iter().all(|&value| value == 0.0) {
+            return Ok(Self::None);
+        }
+        let (k1, k2, p1, p2, k3) = match *coefficients {
+            [k1, k2, p1, p2] => (k1, k2, p1, p2, 0.0),
+            [k1, k2, p1, p2, k3] => (k1, k2, p1, p2, k3),
+            _ => {
+                return Err(Error::LoadCalibrationError {
+                    reason: format!(
+                        "expected 4 or 5 distortion coefficients, but got {}",
+                        coefficients.len()
+                    ),
+                })
+            }
+        };
+        Ok(Self::BrownConrady { k1, k2, k3, p1, p2 })
+    }
+
+    /// Reads fisheye coefficients `k1, k2, k3, k4`.
+    fn from_fisheye(coefficients: &[f64]) -> Result<Self, Error> {
+        let &[k1, k2, k3, k4] = coefficients else {
+            return Err(Error::LoadCalibrationError {
+                reason: format!(
+                    "expected 4 fisheye distortion coefficients, but got {}",
+                    coefficients.len()
+                ),
+            });
+        };
+        Ok(Self::KannalaBrandt { k1, k2, k3, k4 })
+    }
+}
+
+/// A row-major matrix entry shared by the OpenCV and ROS formats.
+#[derive(Deserialize)]
+struct Matrix {
+    data: Vec<f64>,
+}
+
+fn parse_yaml<T>(text: &str) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    serde_yaml::from_str(text).map_err(|error| Error::LoadCalibrationError {
+        reason: format!("invalid calibration YAML: {error}"),
+    })
+}
@@ -0,0 +1,443 @@
+//! Tag families built from user-supplied code tables.
+//!
+//! A [FamilyData] holds the code words and bit layout of a family. It can be
+//! written by hand, taken from a built-in family with [Family::data], or loaded
+//! from the C source files written by the AprilTag family generator with
+//! [FamilyData::from_file]. [CustomFamily] turns it into a family the detector
+//! accepts.
+
+use crate::{
+    error::Error,
+    families::{bits_into_raw, ApriltagFamily},
+    Family,
+};
+use apriltag_sys as sys;
+use std::{
+    collections::BTreeMap,
+    ffi::{c_int, CString},
+    fmt::Write,
+    fs,
+    mem::ManuallyDrop,
+    path::Path,
+    ptr,
+};
+
+/// The code table and bit layout of a tag family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FamilyData {
+    /// The name of the family, which detections report.
+    pub name: String,
+    /// The code words of the tags, indexed by tag ID.
+    pub codes: Vec<u64>,
+    /// The column of each data bit, see [Family::bit_x].
+    pub bit_x: Vec<i32>,
+    /// The row of each data bit, see [Family::bit_y].
+    pub bit_y: Vec<i32>,
+    /// The width in bits of the border square, see [Family::width_at_border].
+    pub width_at_border: usize,
+    /// The width in bits of the whole tag, see [Family::total_width].
+    pub total_width: usize,
+    /// Whether the border is reversed, see [Family::reversed_border].
+    pub reversed_border: bool,
+    /// The minimum Hamming distance between any two codes.
+    pub min_hamming: usize,
+}
+
+impl FamilyData {
+    /// Loads a family from a C source file written by the AprilTag family
+    /// generator, such as the `tag36h11.c` file of the AprilTag library.
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| Error::LoadFamilyError {
+            reason: format!("unable to read '{}': {error}", path.display()),
+        })?;
+        Self::from_c_source(&text)
+    }
+
+    /// Parses the C source of a family written by the AprilTag family generator.
+    ///
+    /// The source assigns the fields of a `tf` family struct, e.g.
+    /// `tf->nbits = 36;` and `tf->bit_x[0] = 1;`, and sets the codes either
+    /// one by one or from a static array initializer.
+    pub fn from_c_source(text: &str) -> Result<Self, Error> {
+        let error = |reason: String| Error::LoadFamilyError { reason };
+        let text = strip_comments(text);
+
+        let mut arrays = BTreeMap::new();
+        let mut fields = BTreeMap::new();
+        let mut indexed: BTreeMap<&str, BTreeMap<usize, &str>> = BTreeMap::new();
+        for statement in text.split(';') {
+            if let Some(start) = statement.find("tf->") {
+                let Some((key, value)) = statement[start + 4..].split_once('=') else {
+                    continue;
+                };
+                let (key, value) = (key.trim(), value.trim());
+                match key.split_once('[') {
+                    Some((field, index)) => {
+                        let index = index.trim_end_matches(']').trim();
+                        let index = parse_int(index)
+                            .and_then(|index| usize::try_from(index).ok())
+                            .ok_or_else(|| error(format!("invalid index in 'tf->{key}'")))?;
+                        indexed
+                            .entry(field.trim())
+                            .or_default()
+                            .insert(index, value);
+                    }
+                    None => {
+                        fields.insert(key, value);
+                    }
+                }
+            } else if let Some((declaration, values)) = statement.split_once('=') {
+                // A static array initializer such as `uint64_t codedata[587] = { ... }`
+                let Some((name, _)) = declaration.split_once('[') else {
+                    continue;
+                };
+                let Some(name) = name.split_whitespace().last() else {
+                    continue;
+                };
+                let values = values.trim().trim_start_matches('{').trim_end_matches('}');
+                arrays.insert(name, values);
+            }
+        }
+
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .copied()
+                .ok_or_else(|| error(format!("missing 'tf->{key}'")))
+        };
+        let int_field = |key: &str| {
+            let value = field(key)?;
+            parse_int(value).ok_or_else(|| error(format!("invalid 'tf->{key}' value '{value}'")))
+        };
+        let usize_field = |key: &str| {
+            usize::try_from(int_field(key)?)
+                .map_err(|_| error(format!("negative 'tf->{key}' value")))
+        };
+
+        let name = field("name")?;
+        let name = name
+            .split('"')
+            .nth(1)
+            .ok_or_else(|| error(format!("invalid 'tf->name' value '{name}'")))?;
+        let reversed_border = match field("reversed_border")? {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            value => {
+                return Err(error(format!(
+                    "invalid 'tf->reversed_border' value '{value}'"
+                )))
+            }
+        };
+        let nbits = usize_field("nbits")?;
+        let ncodes = usize_field("ncodes")?;
+
+        let codes: Vec<u64> = match (indexed.get("codes"), fields.get("codes")) {
+            (Some(codes), _) => table(codes, ncodes, "codes", |value| {
+                parse_int(value).and_then(|value| u64::try_from(value).ok())
+            })?,
+            (None, Some(array)) => {
+                let values = arrays
+                    .get(array)
+                    .ok_or_else(|| error(format!("missing array '{array}'")))?;
+                values
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| {
+                        parse_int(value)
+                            .and_then(|value| u64::try_from(value).ok())
+                            .ok_or_else(|| error(format!("invalid code '{value}'")))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            (None, None) => return Err(error("missing 'tf->codes'".to_string())),
+        };
+        if codes.len() != ncodes {
+            return Err(error(format!(
+                "expected {ncodes} codes, but found {}",
+                codes.len()
+            )));
+        }
+
+        let bits = |key: &str| {
+            let values = indexed
+                .get(key)
+                .ok_or_else(|| error(format!("missing 'tf->{key}'")))?;
+            table(values, nbits, key, |value| {
+                parse_int(value).and_then(|value| i32::try_from(value).ok())
+            })
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            codes,
+            bit_x: bits("bit_x")?,
+            bit_y: bits("bit_y")?,
+            width_at_border: usize_field("width_at_border")?,
+            total_width: usize_field("total_width")?,
+            reversed_border,
+            min_hamming: usize_field("h")?,
+        })
+    }
+
+    /// Writes the family as C source in the style of the AprilTag family
+    /// generator, which [FamilyData::from_c_source] reads back and which
+    /// compiles into the AprilTag library as `<name>_create()` and
+    /// `<name>_destroy()`.
+    pub fn to_c_source(&self) -> String {
+        let name = &self.name;
+        let nbits = self.bit_x.len();
+        let mut source = String::new();
+        source.push_str("#include <stdlib.h>\n#include <string.h>\n#include \"apriltag.h\"\n\n");
+        writeln!(
+            source,
+            "static uint64_t codedata[{}] = {{",
+            self.codes.len()
+        )
+        .unwrap();
+        for code in &self.codes {
+            writeln!(source, "   0x{code:016x}UL,").unwrap();
+        }
+        source.push_str("};\n");
+        writeln!(source, "apriltag_family_t *{name}_create()\n{{").unwrap();
+        source.push_str("   apriltag_family_t *tf = calloc(1, sizeof(apriltag_family_t));\n");
+        writeln!(source, "   tf->name = strdup(\"{name}\");").unwrap();
+        writeln!(source, "   tf->h = {};", self.min_hamming).unwrap();
+        writeln!(source, "   tf->ncodes = {};", self.codes.len()).unwrap();
+        source.push_str("   tf->codes = codedata;\n");
+        writeln!(source, "   tf->nbits = {nbits};").unwrap();
+        writeln!(source, "   tf->bit_x = calloc({nbits}, sizeof(uint32_t));").unwrap();
+        writeln!(source, "   tf->bit_y = calloc({nbits}, sizeof(uint32_t));").unwrap();
+        for (index, (x, y)) in self.bit_x.iter().zip(&self.bit_y).enumerate() {
+            writeln!(source, "   tf->bit_x[{index}] = {x};").unwrap();
+            writeln!(source, "   tf->bit_y[{index}] = {y};").unwrap();
+        }
+        writeln!(source, "   tf->width_at_border = {};", self.width_at_border).unwrap();
+        writeln!(source, "   tf->total_width = {};", self.total_width).unwrap();
+        writeln!(source, "   tf->reversed_border = {};", self.reversed_border).unwrap();
+        source.push_str("   return tf;\n}\n\n");
+        writeln!(
+            source,
+            "void {name}_destroy(apriltag_family_t *tf)\n{{\n   free(tf->bit_x);\n   free(tf->bit_y);\n   free(tf->name);\n   free(tf);\n}}"
+        )
+        .unwrap();
+        source
+    }
+
+    /// Checks that the tables are consistent and fit in the tag.
+    fn validate(&self) -> Result<(), Error> {
+        let error = |reason: String| Err(Error::CreateFamilyError { reason });
+        let nbits = self.bit_x.len();
+
+        if self.name.is_empty() || self.name.contains('\0') {
+            return error(format!("invalid family name '{}'", self.name));
+        }
+        if self.codes.is_empty() || u32::try_from(self.codes.len()).is_err() {
+            return error(format!("invalid number of codes {}", self.codes.len()));
+        }
+        if self.bit_y.len() != nbits {
+            return error(format!(
+                "got {nbits} bit columns but {} bit rows",
+                self.bit_y.len()
+            ));
+        }
+        if !(1..=64).contains(&nbits) {
+            return error(format!("the codes must have 1 to 64 bits, but got {nbits}"));
+        }
+        if let Some(code) = self
+            .codes
+            .iter()
+            .find(|&&code| nbits < 64 && code >> nbits != 0)
+        {
+            return error(format!("the code {code:#x} has more than {nbits} bits"));
+        }
+        if self.width_at_border == 0
+            || self.total_width < self.width_at_border
+            || !(self.total_width - self.width_at_border).is_multiple_of(2)
+            || c_int::try_from(self.total_width).is_err()
+        {
+            return error(format!(
+                "the border width {} does not fit centered in the total width {}",
+                self.width_at_border, self.total_width
+            ));
+        }
+
+        // The bit positions are relative to the border square
+        let offset = ((self.total_width - self.width_at_border) / 2) as i64;
+        let inside =
+            |position: i32| (0..self.total_width as i64).contains(&(position as i64 + offset));
+        if let Some(bit) =
+            (0..nbits).find(|&bit| !inside(self.bit_x[bit]) || !inside(self.bit_y[bit]))
+        {
+            return error(format!(
+                "bit {bit} at ({}, {}) lies outside the tag",
+                self.bit_x[bit], self.bit_y[bit]
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A tag family built from a [FamilyData] code table.
+///
+/// Like the built-in families, it can be passed to
+/// [DetectorBuilder::add_family_bits](crate::DetectorBuilder::add_family_bits)
+/// directly or as a [Family].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct CustomFamily {
+    pub(crate) ptr: *mut sys::apriltag_family_t,
+}
+
+impl CustomFamily {
+    /// Creates a family from the code table, checking that it is consistent.
+    pub fn new(data: FamilyData) -> Result<Self, Error> {
+        data.validate()?;
+        let FamilyData {
+            name,
+            codes,
+            bit_x,
+            bit_y,
+            width_at_border,
+            total_width,
+            reversed_border,
+            min_hamming,
+        } = data;
+
+        let name = CString::new(name).map_err(|_| Error::CreateFamilyError {
+            reason: "the family name contains a NUL byte".to_string(),
+        })?;
+        let family = sys::apriltag_family_t {
+            ncodes: codes.len() as u32,
+            nbits: bit_x.len() as u32,
+            codes: Box::into_raw(codes.into_boxed_slice()) as *mut u64,
+            width_at_border: width_at_border as c_int,
+            total_width: total_width as c_int,
+            reversed_border,
+            bit_x: bits_into_raw(bit_x),
+            bit_y: bits_into_raw(bit_y),
+            h: min_hamming as u32,
+            name: name.into_raw(),
+            impl_: ptr::null_mut(),
+        };
+
+        Ok(Self {
+            ptr: Box::into_raw(Box::new(family)),
+        })
+    }
+}
+
+impl ApriltagFamily for CustomFamily {
+    fn into_raw(self) -> *mut sys::apriltag_family_t {
+        ManuallyDrop::new(self).ptr
+    }
+}
+
+impl Drop for CustomFamily {
+    fn drop(&mut self) {
+        unsafe {
+            let family = Box::from_raw(self.ptr);
+            let (ncodes, nbits) = (family.ncodes as usize, family.nbits as usize);
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                family.codes,
+                ncodes,
+            )));
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                family.bit_x,
+                nbits,
+            )));
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                family.bit_y,
+                nbits,
+            )));
+            drop(CString::from_raw(family.name));
+        }
+    }
+}
+
+/// Collects the indexed assignments of a table, which must cover every index
+/// below `len`.
+fn table<T>(
+    values: &BTreeMap<usize, &str>,
+    len: usize,
+    key: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, Error> {
+    (0..len)
+        .map(|index| {
+            let value = values.get(&index).ok_or_else(|| Error::LoadFamilyError {
+                reason: format!("missing 'tf->{key}[{index}]'"),
+            })?;
+            parse(value).ok_or_else(|| Error::LoadFamilyError {
+                reason: format!("invalid 'tf->{key}[{index}]' value '{value}'"),
+            })
+        })
+        .collect()
+}
+
+/// Parses a C integer literal, e.g. `-2` or `0x0000000d7e00984bUL`.
+fn parse_int(text: &str) -> Option<i128> {
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text.trim_start()),
+        None => (false, text),
+    };
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Removes the `/* */` and `//` comments from C source.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        match (rest.find("/*"), rest.find("//")) {
+            (Some(block), line) if line.is_none_or(|line| block < line) => {
+                stripped.push_str(&rest[..block]);
+                rest = rest[block..]
+                    .find("*/")
+                    .map_or("", |end| &rest[block + end + 2..]);
+            }
+            (_, Some(line)) => {
+                stripped.push_str(&rest[..line]);
+                rest = rest[line..]
+                    .find('\n')
+                    .map_or("", |end| &rest[line + end..]);
+            }
+            _ => {
+                stripped.push_str(rest);
+                return stripped;
+            }
+        }
+    }
+}
+
+impl Family {
+    /// Creates a family from a code table, see [CustomFamily::new].
+    pub fn custom(data: FamilyData) -> Result<Self, Error> {
+        Ok(CustomFamily::new(data)?.into())
+    }
+
+    /// Copies the code table and bit layout of the family.
+    pub fn data(&self) -> FamilyData {
+        FamilyData {
+            name: self.name(),
+            codes: self.codes().to_vec(),
+            bit_x: self.bit_x().to_vec(),
+            bit_y: self.bit_y().to_vec(),
+            width_at_border: self.width_at_border(),
+            total_width: self.total_width(),
+            reversed_border: self.reversed_border(),
+            min_hamming: self.min_hamming(),
+        }
+    }
+}
@@ -6,10 +6,10 @@ use crate::{
 };
 use apriltag_sys as sys;
 use std::{
-    ffi::c_int,
+    ffi::{c_int, CStr},
     fmt::{self, Debug, Formatter},
-    mem::{ManuallyDrop, MaybeUninit},
-    ptr::NonNull,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ptr::{self, NonNull},
 };
 
 /// Represent a marker detection outcome.
@@ -24,6 +24,19 @@ impl Detection {
         unsafe { self.ptr.as_ref().id as usize }
     }
 
+    /// Get the name of the tag family, e.g. `tag36h11`.
+    pub fn family_name(&self) -> String {
+        unsafe {
+            CStr::from_ptr(self.family_raw().name)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
+    pub(crate) fn family_raw(&self) -> &sys::apriltag_family_t {
+        unsafe { &*self.ptr.as_ref().family }
+    }
+
     /// Get the Hamming distance to the target tag.
     pub fn hamming(&self) -> usize {
         unsafe { self.ptr.as_ref().hamming as usize }
@@ -49,76 +62,59 @@ impl Detection {
         unsafe { MatdRef::from_ptr(self.ptr.as_ref().H) }
     }
 
+    /// Creates a copy of the detection with the corners moved to new pixel coordinates.
+    ///
+    /// The homography is refitted to the new corners and the center is projected
+    /// through it, so pose estimation on the copy uses the new corners
+    /// consistently. This is useful to undistort the corners before calling
+    /// [estimate_tag_pose](Detection::estimate_tag_pose).
+    /// Returns `None` if the corners are degenerate.
+    pub fn with_corners(&self, corners: [[f64; 2]; 4]) -> Option<Detection> {
+        let homography = homography_from_corners(&corners)?;
+        let center = project(&homography, [0.0, 0.0]);
+
+        unsafe {
+            let original = self.ptr.as_ref();
+            let matrix = sys::matd_create_data(3, 3, homography.as_ptr());
+            if matrix.is_null() {
+                return None;
+            }
+            let ptr = libc::calloc(1, mem::size_of::<sys::apriltag_detection_t>())
+                as *mut sys::apriltag_detection_t;
+            let Some(ptr) = NonNull::new(ptr) else {
+                sys::matd_destroy(matrix);
+                return None;
+            };
+            ptr.as_ptr().write(sys::apriltag_detection_t {
+                family: original.family,
+                id: original.id,
+                hamming: original.hamming,
+                decision_margin: original.decision_margin,
+                H: matrix,
+                c: center,
+                p: corners,
+            });
+            Some(Self { ptr })
+        }
+    }
+
     /// Estimates the pose of tag with specified number of iterations.
     pub fn estimate_tag_pose_orthogonal_iteration(
         &self,
         params: &TagParams,
         n_iters: usize,
     ) -> Vec<PoseEstimation> {
-        let mut info = sys::apriltag_detection_info_t {
-            det: self.ptr.as_ptr(),
-            tagsize: params.tagsize,
-            fx: params.fx,
-            fy: params.fy,
-            cx: params.cx,
-            cy: params.cy,
-        };
-
-        let poses: Vec<_> = unsafe {
-            let mut pose1: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
-            let mut err1: MaybeUninit<f64> = MaybeUninit::uninit();
-            let mut pose2: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
-            let mut err2: MaybeUninit<f64> = MaybeUninit::uninit();
-
-            sys::estimate_tag_pose_orthogonal_iteration(
-                &mut info as *mut _,
-                err1.as_mut_ptr(),
-                pose1.as_mut_ptr(),
-                err2.as_mut_ptr(),
-                pose2.as_mut_ptr(),
-                n_iters as c_int,
-            );
-
-            let pose1 = pose1.assume_init();
-            let err1 = err1.assume_init();
-
-            let pose2 = pose2.assume_init();
-            let err2 = err2.assume_init();
-
-            let pose1 = (!pose1.R.is_null()).then(|| PoseEstimation {
-                pose: Pose(pose1),
-                error: err1,
-            });
-
-            let pose2 = (!pose2.R.is_null()).then(|| PoseEstimation {
-                pose: Pose(pose2),
-                error: err2,
-            });
-
-            pose1.into_iter().chain(pose2).collect()
-        };
-
-        poses
+        unsafe { estimate_tag_pose_orthogonal_iteration(self.ptr.as_ptr(), params, n_iters) }
     }
 
     /// Estimates the pose of tag.
     pub fn estimate_tag_pose(&self, params: &TagParams) -> Option<Pose> {
-        let mut info = sys::apriltag_detection_info_t {
-            det: self.ptr.as_ptr(),
-            tagsize: params.tagsize,
-            fx: params.fx,
-            fy: params.fy,
-            cx: params.cx,
-            cy: params.cy,
-        };
-
-        unsafe {
-            let mut pose: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
-            sys::estimate_tag_pose(&mut info as *mut _, pose.as_mut_ptr());
-            let pose = pose.assume_init();
+        unsafe { estimate_tag_pose(self.ptr.as_ptr(), params) }
+    }
 
-            (!pose.R.is_null()).then(|| Pose(pose))
-        }
+    /// Copies the detection into an owned [DetectionData].
+    pub fn to_data(&self) -> DetectionData {
+        self.into()
     }
 
     /// Creates an instance from pointer.
@@ -140,6 +136,214 @@ impl Detection {
     }
 }
 
+/// An owned copy of a detection that does not refer to C memory, e.g. to log,
+/// replay or send detections.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectionData {
+    /// The marker ID.
+    pub id: usize,
+    /// The name of the tag family, e.g. `tag36h11`.
+    pub family: String,
+    /// The Hamming distance to the target tag.
+    pub hamming: usize,
+    /// The decision margin of the decoding.
+    pub decision_margin: f32,
+    /// The center of the tag in pixels.
+    pub center: [f64; 2],
+    /// The corners of the tag in pixels, in the order of [Detection::corners].
+    pub corners: [[f64; 2]; 4],
+    /// The homography mapping [TAG_CORNERS] to the corners, as rows.
+    pub homography: [[f64; 3]; 3],
+}
+
+impl DetectionData {
+    /// Estimates the pose of tag with specified number of iterations.
+    pub fn estimate_tag_pose_orthogonal_iteration(
+        &self,
+        params: &TagParams,
+        n_iters: usize,
+    ) -> Vec<PoseEstimation> {
+        self.with_raw(|ptr| unsafe { estimate_tag_pose_orthogonal_iteration(ptr, params, n_iters) })
+            .unwrap_or_default()
+    }
+
+    /// Estimates the pose of tag.
+    pub fn estimate_tag_pose(&self, params: &TagParams) -> Option<Pose> {
+        self.with_raw(|ptr| unsafe { estimate_tag_pose(ptr, params) })
+            .flatten()
+    }
+
+    /// Runs the function on a temporary C detection with the data.
+    ///
+    /// The detection has no family, which pose estimation does not need.
+    /// Returns `None` if the homography cannot be allocated.
+    fn with_raw<T>(&self, f: impl FnOnce(*mut sys::apriltag_detection_t) -> T) -> Option<T> {
+        let homography = self.homography.concat();
+        unsafe {
+            let matrix = sys::matd_create_data(3, 3, homography.as_ptr());
+            if matrix.is_null() {
+                return None;
+            }
+            let mut detection = sys::apriltag_detection_t {
+                family: ptr::null_mut(),
+                id: self.id as c_int,
+                hamming: self.hamming as c_int,
+                decision_margin: self.decision_margin,
+                H: matrix,
+                c: self.center,
+                p: self.corners,
+            };
+            let result = f(&mut detection);
+            sys::matd_destroy(matrix);
+            Some(result)
+        }
+    }
+}
+
+impl From<&Detection> for DetectionData {
+    fn from(detection: &Detection) -> Self {
+        let homography = detection.homography();
+        let data = homography.data();
+        Self {
+            id: detection.id(),
+            family: detection.family_name(),
+            hamming: detection.hamming(),
+            decision_margin: detection.decision_margin(),
+            center: detection.center(),
+            corners: detection.corners(),
+            homography: [
+                [data[0], data[1], data[2]],
+                [data[3], data[4], data[5]],
+                [data[6], data[7], data[8]],
+            ],
+        }
+    }
+}
+
+impl From<Detection> for DetectionData {
+    fn from(detection: Detection) -> Self {
+        (&detection).into()
+    }
+}
+
+unsafe fn estimate_tag_pose_orthogonal_iteration(
+    detection: *mut sys::apriltag_detection_t,
+    params: &TagParams,
+    n_iters: usize,
+) -> Vec<PoseEstimation> {
+    let mut info = sys::apriltag_detection_info_t {
+        det: detection,
+        tagsize: params.tagsize,
+        fx: params.fx,
+        fy: params.fy,
+        cx: params.cx,
+        cy: params.cy,
+    };
+
+    let mut pose1: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
+    let mut err1: MaybeUninit<f64> = MaybeUninit::uninit();
+    let mut pose2: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
+    let mut err2: MaybeUninit<f64> = MaybeUninit::uninit();
+
+    sys::estimate_tag_pose_orthogonal_iteration(
+        &mut info as *mut _,
+        err1.as_mut_ptr(),
+        pose1.as_mut_ptr(),
+        err2.as_mut_ptr(),
+        pose2.as_mut_ptr(),
+        n_iters as c_int,
+    );
+
+    let pose1 = pose1.assume_init();
+    let err1 = err1.assume_init();
+
+    let pose2 = pose2.assume_init();
+    let err2 = err2.assume_init();
+
+    let pose1 = (!pose1.R.is_null()).then(|| PoseEstimation {
+        pose: Pose(pose1),
+        error: err1,
+    });
+
+    let pose2 = (!pose2.R.is_null()).then(|| PoseEstimation {
+        pose: Pose(pose2),
+        error: err2,
+    });
+
+    pose1.into_iter().chain(pose2).collect()
+}
+
+unsafe fn estimate_tag_pose(
+    detection: *mut sys::apriltag_detection_t,
+    params: &TagParams,
+) -> Option<Pose> {
+    let mut info = sys::apriltag_detection_info_t {
+        det: detection,
+        tagsize: params.tagsize,
+        fx: params.fx,
+        fy: params.fy,
+        cx: params.cx,
+        cy: params.cy,
+    };
+
+    let mut pose: MaybeUninit<sys::apriltag_pose_t> = MaybeUninit::uninit();
+    sys::estimate_tag_pose(&mut info as *mut _, pose.as_mut_ptr());
+    let pose = pose.assume_init();
+
+    (!pose.R.is_null()).then(|| Pose(pose))
+}
+
+// SAFETY: A detection exclusively owns its C struct and only reads the family
+// it points to, which is never freed or modified after detection.
+unsafe impl Send for Detection {}
+unsafe impl Sync for Detection {}
+
+/// The corners of the ideal tag in the order of [Detection::corners], which the
+/// homography maps to pixels.
+pub const TAG_CORNERS: [[f64; 2]; 4] = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+
+/// Fits the row-major homography mapping [TAG_CORNERS] to `corners`.
+fn homography_from_corners(corners: &[[f64; 2]; 4]) -> Option<[f64; 9]> {
+    // Solve the 8x8 direct linear transform system with the last entry fixed to 1
+    let mut system = [[0.0; 9]; 8];
+    for (index, ([x, y], [u, v])) in TAG_CORNERS.iter().zip(corners).enumerate() {
+        system[2 * index] = [*x, *y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, *u];
+        system[2 * index + 1] = [0.0, 0.0, 0.0, *x, *y, 1.0, -x * v, -y * v, *v];
+    }
+
+    for col in 0..8 {
+        let pivot = (col..8)
+            .max_by(|&lhs, &rhs| system[lhs][col].abs().total_cmp(&system[rhs][col].abs()))?;
+        if system[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        system.swap(col, pivot);
+        let pivot_row = system[col];
+        for (index, row) in system.iter_mut().enumerate() {
+            if index != col {
+                let factor = row[col] / pivot_row[col];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(col) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let mut homography = [1.0; 9];
+    for (index, row) in system.iter().enumerate() {
+        homography[index] = row[8] / row[index];
+    }
+    Some(homography)
+}
+
+/// Projects a point through a row-major homography.
+fn project(homography: &[f64; 9], [x, y]: [f64; 2]) -> [f64; 2] {
+    let [h00, h01, h02, h10, h11, h12, h20, h21, h22] = *homography;
+    let z = h20 * x + h21 * y + h22;
+    [(h00 * x + h01 * y + h02) / z, (h10 * x + h11 * y + h12) / z]
+}
+
 impl Debug for Detection {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         formatter
@@ -156,6 +156,11 @@ impl Detector {
         }
     }
 
+    /// Get the thresholds for detecting quads as candidates for further processing.
+    pub fn thresholds(&self) -> QuadThresholds {
+        unsafe { QuadThresholds::from_c_params(&self.ptr.as_ref().qtp) }
+    }
+
     /// Set various thresholds for detecting quads as candidates for further processing.
     pub fn set_thresholds(&mut self, thresholds: QuadThresholds) {
         unsafe {
@@ -182,6 +187,12 @@ impl Detector {
     }
 }
 
+// SAFETY: The detector exclusively owns its C state, and the library keeps no
+// thread-local state, so it can be moved to another thread. Detection needs a
+// mutable reference, so shared references only read the parameters.
+unsafe impl Send for Detector {}
+unsafe impl Sync for Detector {}
+
 impl Drop for Detector {
     fn drop(&mut self) {
         unsafe { sys::apriltag_detector_destroy(self.ptr.as_ptr()) };
@@ -189,7 +200,14 @@ impl Drop for Detector {
 }
 
 /// The adjustable theshold for detecting candidates.
+///
+/// With the `serde` feature, the angle is serialized in radians.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "QuadThresholdsRepr", try_from = "QuadThresholdsRepr")
+)]
 pub struct QuadThresholds {
     /// Enforce a minimal number of pixels per candidate.
     pub min_cluster_pixels: u32,
@@ -198,14 +216,10 @@ pub struct QuadThresholds {
     /// group of pixels into a quad.
     pub max_maxima_number: u32,
 
-    /// Reject quads where pairs of edges have angles that are close
-    /// to a straight lines.
+    /// Reject quads where pairs of adjacent edges are within this angle
+    /// of being parallel, that is corners close to 0 or 180 degrees.
     pub min_angle: Angle,
 
-    /// Reject quads where pairs of edges have angles that are close
-    /// to a 180 degrees.
-    pub min_opposite_angle: Angle,
-
     /// Specify the maximal mean squared error when fittings lines to
     /// the contour. Useful for performance evaluation.
     pub max_mse: R32,
@@ -218,23 +232,77 @@ pub struct QuadThresholds {
     pub deglitch: bool,
 }
 
+/// The serialized form of [QuadThresholds] with plain numbers.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct QuadThresholdsRepr {
+    min_cluster_pixels: u32,
+    max_maxima_number: u32,
+    min_angle: f64,
+    max_mse: f32,
+    min_white_black_diff: u8,
+    deglitch: bool,
+}
+
+#[cfg(feature = "serde")]
+impl From<QuadThresholds> for QuadThresholdsRepr {
+    fn from(thresholds: QuadThresholds) -> Self {
+        Self {
+            min_cluster_pixels: thresholds.min_cluster_pixels,
+            max_maxima_number: thresholds.max_maxima_number,
+            min_angle: thresholds.min_angle.as_radians(),
+            max_mse: thresholds.max_mse.raw(),
+            min_white_black_diff: thresholds.min_white_black_diff,
+            deglitch: thresholds.deglitch,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<QuadThresholdsRepr> for QuadThresholds {
+    type Error = String;
+
+    fn try_from(repr: QuadThresholdsRepr) -> Result<Self, Self::Error> {
+        Ok(Self {
+            min_cluster_pixels: repr.min_cluster_pixels,
+            max_maxima_number: repr.max_maxima_number,
+            min_angle: Angle::from_radians(repr.min_angle),
+            max_mse: R32::try_new(repr.max_mse)
+                .ok_or_else(|| format!("invalid max_mse {}", repr.max_mse))?,
+            min_white_black_diff: repr.min_white_black_diff,
+            deglitch: repr.deglitch,
+        })
+    }
+}
+
 impl QuadThresholds {
-    fn to_c_params(&self) -> sys::apriltag_quad_thresh_params {
+    fn from_c_params(params: &sys::apriltag_quad_thresh_params) -> Self {
+        Self {
+            min_cluster_pixels: params.min_cluster_pixels as u32,
+            max_maxima_number: params.max_nmaxima as u32,
+            // The library only reads the cosine, its create function leaves the angle unset
+            min_angle: Angle::from_radians((params.cos_critical_rad as f64).acos()),
+            max_mse: R32::new(params.max_line_fit_mse),
+            min_white_black_diff: params.min_white_black_diff as u8,
+            deglitch: params.deglitch != 0,
+        }
+    }
+
+    fn to_c_params(self) -> sys::apriltag_quad_thresh_params {
         let Self {
             min_cluster_pixels,
             max_maxima_number,
             min_angle,
-            min_opposite_angle,
             max_mse,
             min_white_black_diff,
             deglitch,
-        } = *self;
+        } = self;
 
         sys::apriltag_quad_thresh_params {
             min_cluster_pixels: min_cluster_pixels as c_int,
             max_nmaxima: max_maxima_number as c_int,
             critical_rad: min_angle.as_radians() as f32,
-            cos_critical_rad: min_opposite_angle.as_radians() as f32,
+            cos_critical_rad: min_angle.as_radians().cos() as f32,
             max_line_fit_mse: max_mse.raw(),
             min_white_black_diff: min_white_black_diff as c_int,
             deglitch: deglitch as c_int,
@@ -0,0 +1,128 @@
+//! A pool of detectors shared by several threads.
+
+use crate::{detection::Detection, detector::Detector, error::Error, image_buf::Image};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex, PoisonError},
+};
+
+/// A pool of detectors for running detection on several threads at once.
+///
+/// A [Detector] needs a mutable reference to detect tags, so it serves one
+/// thread at a time. The pool hands out its detectors to the calling threads
+/// and blocks when all of them are in use. It is `Sync`, so it can be shared by
+/// reference, e.g. with [std::thread::scope], or in an `Arc`.
+///
+/// Each detector also runs its own worker threads, see
+/// [set_thread_number](Detector::set_thread_number).
+#[derive(Debug)]
+pub struct DetectorPool {
+    detectors: Mutex<Vec<Detector>>,
+    available: Condvar,
+    size: usize,
+}
+
+impl DetectorPool {
+    /// Creates a pool of `size` detectors created by `build`, which is called
+    /// once per detector, e.g. with a fresh [DetectorBuilder](crate::DetectorBuilder).
+    pub fn new<F>(size: usize, mut build: F) -> Result<Self, Error>
+    where
+        F: FnMut() -> Result<Detector, Error>,
+    {
+        if size == 0 {
+            return Err(Error::CreateDetectorError {
+                reason: "a detector pool needs at least one detector".to_string(),
+            });
+        }
+        let detectors = (0..size).map(|_| build()).collect::<Result<_, _>>()?;
+        Ok(Self {
+            detectors: Mutex::new(detectors),
+            available: Condvar::new(),
+            size,
+        })
+    }
+
+    /// Gets the number of detectors in the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Runs detection on the input image with the next free detector.
+    pub fn detect(&self, image: &Image) -> Vec<Detection> {
+        self.get().detect(image)
+    }
+
+    /// Takes a detector out of the pool until the returned guard is dropped,
+    /// waiting for one to be returned if all of them are in use.
+    pub fn get(&self) -> PooledDetector<'_> {
+        let mut detectors = self
+            .available
+            .wait_while(
+                self.detectors
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+                |detectors| detectors.is_empty(),
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        let detector = detectors.pop().expect("a detector is available");
+        PooledDetector {
+            pool: self,
+            detector: Some(detector),
+        }
+    }
+
+    /// Applies the function to every detector, waiting until all of them are
+    /// back in the pool, e.g. to change the detection parameters.
+    ///
+    /// It deadlocks if the calling thread holds a [PooledDetector].
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut Detector),
+    {
+        let mut detectors = self
+            .available
+            .wait_while(
+                self.detectors
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+                |detectors| detectors.len() < self.size,
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        detectors.iter_mut().for_each(&mut f);
+    }
+}
+
+/// A detector taken out of a [DetectorPool], which it returns to when dropped.
+#[derive(Debug)]
+pub struct PooledDetector<'a> {
+    pool: &'a DetectorPool,
+    detector: Option<Detector>,
+}
+
+impl Deref for PooledDetector<'_> {
+    type Target = Detector;
+
+    fn deref(&self) -> &Self::Target {
+        self.detector.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledDetector<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.detector.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledDetector<'_> {
+    fn drop(&mut self) {
+        if let Some(detector) = self.detector.take() {
+            self.pool
+                .detectors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(detector);
+            // Wake everyone, as for_each() waits for all detectors at once
+            self.pool.available.notify_all();
+        }
+    }
+}
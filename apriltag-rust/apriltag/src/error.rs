@@ -11,4 +11,19 @@ pub enum Error {
 
     #[error("Unable to create a detector: {reason}")]
     CreateDetectorError { reason: String },
+
+    #[error("Unable to create a family: {reason}")]
+    CreateFamilyError { reason: String },
+
+    #[error("Unable to create a tag layout: {reason}")]
+    CreateLayoutError { reason: String },
+
+    #[error("Unable to load a family: {reason}")]
+    LoadFamilyError { reason: String },
+
+    #[error("Unable to render a tag: {reason}")]
+    RenderTagError { reason: String },
+
+    #[error("Unable to load a calibration: {reason}")]
+    LoadCalibrationError { reason: String },
 }
@@ -4,9 +4,9 @@
 //! The images of pre-generated tags can be found at the official repositoy
 //! [https://github.com/AprilRobotics/apriltag-imgs](https://github.com/AprilRobotics/apriltag-imgs).
 
-use crate::error::Error;
+use crate::{custom_family::CustomFamily, error::Error, image_buf::Image};
 use apriltag_sys as sys;
-use std::{fmt::Debug, mem::ManuallyDrop, str::FromStr};
+use std::{ffi::CStr, fmt::Debug, mem::ManuallyDrop, ptr::NonNull, str::FromStr};
 
 pub trait ApriltagFamily
 where
@@ -76,7 +76,8 @@ declare_family!(
 
 /// Represent a family of pre-generated tags.
 ///
-/// It can be instantiated by calling member methods or by [Family::from_str].
+/// It can be instantiated by calling member methods or by [Family::from_str],
+/// or built from a code table with [Family::custom].
 ///
 /// ```rust
 /// use apriltag::Family;
@@ -92,6 +93,13 @@ pub enum Family {
     TagStandard41h12(TagStandard41h12),
     TagStandard52h13(TagStandard52h13),
     TagCustom48h12(TagCustom48h12),
+    Custom(CustomFamily),
+}
+
+impl From<CustomFamily> for Family {
+    fn from(v: CustomFamily) -> Self {
+        Self::Custom(v)
+    }
 }
 
 impl From<TagCustom48h12> for Family {
@@ -153,6 +161,7 @@ impl ApriltagFamily for Family {
             Family::TagStandard41h12(family) => family.into_raw(),
             Family::TagStandard52h13(family) => family.into_raw(),
             Family::TagCustom48h12(family) => family.into_raw(),
+            Family::Custom(family) => family.into_raw(),
         }
     }
 }
@@ -197,6 +206,113 @@ impl Family {
     pub fn tag_standard_52h13() -> Self {
         TagStandard52h13::default().into()
     }
+
+    /// Get the name of the family, e.g. `tag36h11`.
+    pub fn name(&self) -> String {
+        unsafe {
+            CStr::from_ptr(self.raw().name)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
+    /// Get the number of tags in the family.
+    pub fn tag_count(&self) -> usize {
+        self.raw().ncodes as usize
+    }
+
+    /// Get the minimum Hamming distance between any two codes of the family.
+    pub fn min_hamming(&self) -> usize {
+        self.raw().h as usize
+    }
+
+    /// Get the ratio between the width of the border square the detector
+    /// locates and the width of the whole tag.
+    pub fn border_ratio(&self) -> f64 {
+        self.width_at_border() as f64 / self.total_width() as f64
+    }
+
+    /// Get the width in bits of the border square the detector locates, which
+    /// is the square the tag size refers to in pose estimation.
+    pub fn width_at_border(&self) -> usize {
+        self.raw().width_at_border as usize
+    }
+
+    /// Get the width in bits of the whole tag, including the data bits outside
+    /// the border for the standard and circle families.
+    pub fn total_width(&self) -> usize {
+        self.raw().total_width as usize
+    }
+
+    /// Get the code words of the tags, indexed by tag ID.
+    pub fn codes(&self) -> &[u64] {
+        let family = self.raw();
+        unsafe { slice_from_raw(family.codes, family.ncodes as usize) }
+    }
+
+    /// Get the number of data bits in each code word.
+    pub fn nbits(&self) -> usize {
+        self.raw().nbits as usize
+    }
+
+    /// Get the column of each data bit, most significant bit first.
+    ///
+    /// The columns are counted from the left edge of the border square, so
+    /// they are negative or beyond [width_at_border](Family::width_at_border)
+    /// for the data bits outside the border.
+    pub fn bit_x(&self) -> &[i32] {
+        let family = self.raw();
+        unsafe { bits_from_raw(family.bit_x, family.nbits as usize) }
+    }
+
+    /// Get the row of each data bit, most significant bit first, counted from
+    /// the top edge of the border square like [bit_x](Family::bit_x).
+    pub fn bit_y(&self) -> &[i32] {
+        let family = self.raw();
+        unsafe { bits_from_raw(family.bit_y, family.nbits as usize) }
+    }
+
+    /// Check whether the border square is white on a black outline instead of
+    /// black on white, as for the standard and circle families.
+    pub fn reversed_border(&self) -> bool {
+        self.raw().reversed_border
+    }
+
+    /// Render the tag with the given ID with one pixel per bit.
+    ///
+    /// The image is [total_width](Family::total_width) pixels wide, with white
+    /// pixels set to 255 and black pixels to 0.
+    pub fn tag_image(&self, id: usize) -> Result<Image, Error> {
+        let ncodes = self.tag_count();
+        if id >= ncodes {
+            return Err(Error::RenderTagError {
+                reason: format!("the family has {ncodes} tags, but the ID is {id}"),
+            });
+        }
+        let ptr = unsafe { sys::apriltag_to_image(self.as_ptr(), id as u32) };
+        let ptr = NonNull::new(ptr).ok_or_else(|| Error::RenderTagError {
+            reason: "apriltag_to_image() failed".to_string(),
+        })?;
+        Ok(Image { ptr })
+    }
+
+    pub(crate) fn raw(&self) -> &sys::apriltag_family_t {
+        unsafe { &*self.as_ptr() }
+    }
+
+    fn as_ptr(&self) -> *mut sys::apriltag_family_t {
+        match self {
+            Family::Tag16h5(family) => family.ptr,
+            Family::Tag25h9(family) => family.ptr,
+            Family::Tag36h11(family) => family.ptr,
+            Family::TagCircle21h7(family) => family.ptr,
+            Family::TagCircle49h12(family) => family.ptr,
+            Family::TagStandard41h12(family) => family.ptr,
+            Family::TagStandard52h13(family) => family.ptr,
+            Family::TagCustom48h12(family) => family.ptr,
+            Family::Custom(family) => family.ptr,
+        }
+    }
 }
 
 impl FromStr for Family {
@@ -228,3 +344,52 @@ impl FromStr for Family {
         Ok(family)
     }
 }
+
+/// Serializes the family as its name. Custom families are serialized by name
+/// too, but only the built-in families can be deserialized, see
+/// [FamilyData] for custom code tables.
+///
+/// [FamilyData]: crate::FamilyData
+#[cfg(feature = "serde")]
+impl serde::Serialize for Family {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Family {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Borrows a table owned by a family, tolerating null pointers to empty tables.
+/// Reads bit coordinates of a family, which the C library stores in `uint32_t`
+/// with the negative columns wrapped around.
+unsafe fn bits_from_raw<'a>(bits: *const u32, len: usize) -> &'a [i32] {
+    slice_from_raw(bits as *const i32, len)
+}
+
+/// Allocates bit coordinates in the layout read by [bits_from_raw].
+pub(crate) fn bits_into_raw(bits: Vec<i32>) -> *mut u32 {
+    let bits: Box<[u32]> = bits.into_iter().map(|bit| bit as u32).collect();
+    Box::into_raw(bits) as *mut u32
+}
+
+unsafe fn slice_from_raw<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
@@ -0,0 +1,376 @@
+//! Generation of tag family code tables.
+//!
+//! [FamilyGenerator] implements the lexicode search of the AprilTag family
+//! generator: it walks through all codes of a [Layout] in a fixed
+//! pseudo-random order and keeps every code that is far enough, in Hamming
+//! distance, from all rotations of itself and of the codes kept before. The
+//! result is a [FamilyData] for [Family::custom](crate::Family::custom).
+
+use crate::{custom_family::FamilyData, error::Error};
+
+/// The step between successive candidate codes. It is odd, so the walk visits
+/// every code once.
+const CODE_STEP: u64 = 982_451_653;
+
+/// The kind of a cell of a tag layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Black,
+    White,
+    Data,
+    /// A cell that is not part of the tag, e.g. the corners of circle tags.
+    Ignored,
+}
+
+/// The arrangement of the border and data bits of a tag.
+///
+/// The data bits are ordered in four quarters that map onto each other when
+/// the tag is rotated by 90 degrees, followed by the center bit, if any, as
+/// the AprilTag decoder expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// The cells in row-major order.
+    cells: Vec<Cell>,
+    total_width: usize,
+    width_at_border: usize,
+    reversed_border: bool,
+    bit_x: Vec<i32>,
+    bit_y: Vec<i32>,
+}
+
+impl Layout {
+    /// Creates the layout of the classic families, such as tag36h11, with a
+    /// square of data bits inside a one bit wide black border.
+    ///
+    /// The border square is `width_at_border` bits wide, so the tag has
+    /// `(width_at_border - 2)²` data bits.
+    pub fn classic(width_at_border: usize) -> Result<Self, Error> {
+        let total_width = width_at_border + 2;
+        Self::from_rings(total_width, |ring| match ring {
+            0 => Cell::White,
+            1 => Cell::Black,
+            _ => Cell::Data,
+        })
+    }
+
+    /// Creates the layout of the standard families, such as tagStandard41h12,
+    /// with a ring of data bits around a black and a white ring and a square
+    /// of data bits inside them.
+    pub fn standard(total_width: usize) -> Result<Self, Error> {
+        Self::from_rings(total_width, |ring| match ring {
+            1 => Cell::Black,
+            2 => Cell::White,
+            _ => Cell::Data,
+        })
+    }
+
+    /// Parses a layout drawn as rows of cells, separated by newlines or `/`.
+    ///
+    /// Each cell is `d` for a data bit, `b` for black, `w` for white or `x`
+    /// for a cell that is not part of the tag. The border square is found as
+    /// the outermost pair of a white and a black ring, e.g.
+    ///
+    /// ```text
+    /// wwwwww
+    /// wbbbbw
+    /// wbddbw
+    /// wbddbw
+    /// wbbbbw
+    /// wwwwww
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let error = |reason: String| Error::CreateLayoutError { reason };
+        let rows: Vec<&str> = text
+            .split(['\n', '/'])
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let total_width = rows.len();
+        let mut cells = Vec::with_capacity(total_width * total_width);
+        for row in &rows {
+            if row.chars().count() != total_width {
+                return Err(error(format!(
+                    "the layout must be square, but has a row '{row}' in {total_width} rows"
+                )));
+            }
+            for cell in row.chars() {
+                cells.push(match cell {
+                    'b' => Cell::Black,
+                    'w' => Cell::White,
+                    'd' => Cell::Data,
+                    'x' => Cell::Ignored,
+                    _ => return Err(error(format!("unknown cell '{cell}'"))),
+                });
+            }
+        }
+        Self::from_cells(cells, total_width)
+    }
+
+    /// Builds a layout of concentric rings of cells.
+    fn from_rings(total_width: usize, ring_cell: impl Fn(usize) -> Cell) -> Result<Self, Error> {
+        let cells = (0..total_width * total_width)
+            .map(|index| {
+                let (x, y) = (index % total_width, index / total_width);
+                let ring = x.min(y).min(total_width - 1 - x).min(total_width - 1 - y);
+                ring_cell(ring)
+            })
+            .collect();
+        Self::from_cells(cells, total_width)
+    }
+
+    fn from_cells(cells: Vec<Cell>, total_width: usize) -> Result<Self, Error> {
+        let error = |reason: String| Err(Error::CreateLayoutError { reason });
+        let n = total_width;
+        let rotate = |(x, y): (usize, usize)| (n - 1 - y, x);
+        let cell = |(x, y): (usize, usize)| cells[y * n + x];
+
+        let positions = (0..n).flat_map(|y| (0..n).map(move |x| (x, y)));
+        if let Some((x, y)) = positions
+            .clone()
+            .find(|&pos| cell(pos) != cell(rotate(pos)))
+        {
+            return error(format!(
+                "the layout is not symmetric under rotation at cell ({x}, {y})"
+            ));
+        }
+
+        // The border square is the edge between the outermost white and black rings
+        let ring = |ring: usize| {
+            let mut kinds = positions
+                .clone()
+                .filter(|&(x, y)| x.min(y).min(n - 1 - x).min(n - 1 - y) == ring)
+                .map(cell);
+            let first = kinds.next()?;
+            kinds.all(|kind| kind == first).then_some(first)
+        };
+        let Some((border_ring, reversed_border)) =
+            (0..n / 2).find_map(|index| match (ring(index), ring(index + 1)) {
+                (Some(Cell::White), Some(Cell::Black)) => Some((index + 1, false)),
+                (Some(Cell::Black), Some(Cell::White)) => Some((index + 1, true)),
+                _ => None,
+            })
+        else {
+            return error("the layout has no adjacent white and black rings".to_string());
+        };
+
+        // Collect the data bits one rotation orbit at a time, so that the
+        // quarters of the bits map onto each other under rotation
+        let mut quarter = vec![];
+        let mut center = None;
+        let mut visited = vec![false; n * n];
+        for pos in positions.filter(|&pos| cell(pos) == Cell::Data) {
+            if visited[pos.1 * n + pos.0] {
+                continue;
+            }
+            if rotate(pos) == pos {
+                center = Some(pos);
+            } else {
+                quarter.push(pos);
+            }
+            let mut orbit = pos;
+            for _ in 0..4 {
+                visited[orbit.1 * n + orbit.0] = true;
+                orbit = rotate(orbit);
+            }
+        }
+        let mut bits: Vec<_> = (0..4)
+            .flat_map(|turns| {
+                quarter
+                    .iter()
+                    .map(move |&pos| (0..turns).fold(pos, |pos, _| rotate(pos)))
+            })
+            .collect();
+        bits.extend(center);
+        if !(1..=64).contains(&bits.len()) {
+            return error(format!(
+                "the layout must have 1 to 64 data bits, but has {}",
+                bits.len()
+            ));
+        }
+
+        let offset = border_ring as i32;
+        Ok(Self {
+            cells,
+            total_width,
+            width_at_border: n - 2 * border_ring,
+            reversed_border,
+            bit_x: bits.iter().map(|&(x, _)| x as i32 - offset).collect(),
+            bit_y: bits.iter().map(|&(_, y)| y as i32 - offset).collect(),
+        })
+    }
+
+    /// Gets the number of data bits.
+    pub fn nbits(&self) -> usize {
+        self.bit_x.len()
+    }
+
+    /// Gets the width in bits of the border square.
+    pub fn width_at_border(&self) -> usize {
+        self.width_at_border
+    }
+
+    /// Gets the width in bits of the whole tag.
+    pub fn total_width(&self) -> usize {
+        self.total_width
+    }
+
+    /// Checks whether the border square is white on a black outline.
+    pub fn reversed_border(&self) -> bool {
+        self.reversed_border
+    }
+
+    /// Gets the column of each data bit relative to the border square.
+    pub fn bit_x(&self) -> &[i32] {
+        &self.bit_x
+    }
+
+    /// Gets the row of each data bit relative to the border square.
+    pub fn bit_y(&self) -> &[i32] {
+        &self.bit_y
+    }
+
+    /// Rotates a code by 90 degrees in the same way as the AprilTag decoder.
+    pub fn rotate90(&self, code: u64) -> u64 {
+        let nbits = self.nbits();
+        let (bits, center) = if nbits % 4 == 1 {
+            (nbits - 1, 1)
+        } else {
+            (nbits, 0)
+        };
+        let quarter = bits / 4;
+        let rotated = ((code >> center) << (quarter + center))
+            | ((code >> (3 * quarter + center)) << center)
+            | (code & center as u64);
+        rotated & self.mask()
+    }
+
+    /// Counts the black rectangles a greedy cover needs to draw the tag with
+    /// the code, a measure of how unlike a simple shape the tag looks.
+    pub fn complexity(&self, code: u64) -> usize {
+        let n = self.total_width;
+        let mut black: Vec<bool> = self.cells.iter().map(|&cell| cell == Cell::Black).collect();
+        let offset = (n - self.width_at_border) as i32 / 2;
+        let nbits = self.nbits();
+        for (bit, (&x, &y)) in self.bit_x.iter().zip(&self.bit_y).enumerate() {
+            let index = (y + offset) as usize * n + (x + offset) as usize;
+            black[index] = code & (1 << (nbits - bit - 1)) == 0;
+        }
+
+        let mut rectangles = 0;
+        for y in 0..n {
+            for x in 0..n {
+                if !black[y * n + x] {
+                    continue;
+                }
+                let width = (x..n).take_while(|&x| black[y * n + x]).count();
+                let height = (y..n)
+                    .take_while(|&y| (x..x + width).all(|x| black[y * n + x]))
+                    .count();
+                for y in y..y + height {
+                    for x in x..x + width {
+                        black[y * n + x] = false;
+                    }
+                }
+                rectangles += 1;
+            }
+        }
+        rectangles
+    }
+
+    fn mask(&self) -> u64 {
+        match self.nbits() {
+            64 => u64::MAX,
+            nbits => (1 << nbits) - 1,
+        }
+    }
+}
+
+/// Searches a tag family of codes with a minimum Hamming distance.
+///
+/// The search is deterministic, so the same settings always produce the same
+/// codes in the same order. It tries every code of the layout, which takes a
+/// long time for layouts with many bits unless the number of codes is capped.
+#[derive(Debug, Clone)]
+pub struct FamilyGenerator {
+    layout: Layout,
+    min_hamming: usize,
+    min_complexity: usize,
+    max_codes: Option<usize>,
+}
+
+impl FamilyGenerator {
+    /// Creates a generator for codes of the layout that differ in at least
+    /// `min_hamming` bits from each other under any rotation.
+    pub fn new(layout: Layout, min_hamming: usize) -> Self {
+        Self {
+            layout,
+            min_hamming,
+            min_complexity: 0,
+            max_codes: None,
+        }
+    }
+
+    /// Rejects codes whose tags have a lower [complexity](Layout::complexity).
+    pub fn min_complexity(mut self, min_complexity: usize) -> Self {
+        self.min_complexity = min_complexity;
+        self
+    }
+
+    /// Stops the search after finding the given number of codes.
+    pub fn max_codes(mut self, max_codes: usize) -> Self {
+        self.max_codes = Some(max_codes);
+        self
+    }
+
+    /// Runs the search and returns the family with the given name.
+    pub fn generate(&self, name: &str) -> Result<FamilyData, Error> {
+        let layout = &self.layout;
+        let max_codes = self.max_codes.unwrap_or(usize::MAX);
+        let far = |lhs: u64, rhs: u64| (lhs ^ rhs).count_ones() as usize >= self.min_hamming;
+
+        let mut codes = vec![];
+        let mut rotations = vec![];
+        let mut code = 0u64;
+        for _ in 0..=layout.mask() {
+            if codes.len() >= max_codes {
+                break;
+            }
+            code = code.wrapping_add(CODE_STEP) & layout.mask();
+            if layout.complexity(code) < self.min_complexity {
+                continue;
+            }
+
+            let rotated = [
+                code,
+                layout.rotate90(code),
+                layout.rotate90(layout.rotate90(code)),
+                layout.rotate90(layout.rotate90(layout.rotate90(code))),
+            ];
+            if rotated[1..].iter().all(|&rotation| far(code, rotation))
+                && rotations.iter().all(|&other| far(code, other))
+            {
+                codes.push(code);
+                rotations.extend(rotated);
+            }
+        }
+
+        if codes.is_empty() {
+            return Err(Error::CreateFamilyError {
+                reason: format!(
+                    "no code of the layout has a minimum Hamming distance of {} to its rotations",
+                    self.min_hamming
+                ),
+            });
+        }
+        Ok(FamilyData {
+            name: name.to_string(),
+            codes,
+            bit_x: layout.bit_x.clone(),
+            bit_y: layout.bit_y.clone(),
+            width_at_border: layout.width_at_border,
+            total_width: layout.total_width,
+            reversed_border: layout.reversed_border,
+            min_hamming: self.min_hamming,
+        })
+    }
+}
@@ -253,6 +253,10 @@ impl Clone for Image {
     }
 }
 
+// SAFETY: The image exclusively owns its buffer, and the detector only reads it.
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
 impl Drop for Image {
     fn drop(&mut self) {
         unsafe {
@@ -277,8 +281,7 @@ mod tests {
 
         // invert color of to_image
         (0..height)
-            .into_iter()
-            .flat_map(|y| (0..width).into_iter().map(move |x| (x, y)))
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
             .for_each(|(x, y)| {
                 assert_eq!(from_image[(x, y)], to_image[(x, y)]);
             });
@@ -286,7 +289,7 @@ mod tests {
 
     fn diagonal_image(width: usize, height: usize) -> Image {
         let mut image = Image::zeros_with_alignment(width, height, DEFAULT_ALIGNMENT_U8).unwrap();
-        (0..width.min(height)).into_iter().for_each(|index| {
+        (0..width.min(height)).for_each(|index| {
             image[(index, index)] = 255;
         });
         image
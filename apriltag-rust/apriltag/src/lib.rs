@@ -8,21 +8,35 @@
 //!
 //! - **apriltag-nalgebra**: Add conversions from/to two dimensional byte matrix in nalgebra crate.
 //! - **apriltag-image**: Add conversions from/to image types in image crate.
+//!
+//! The `calibration` feature adds loaders for camera calibration files in the
+//! [calibration] module.
+//!
+//! The `serde` feature implements `Serialize` and `Deserialize` for the tag
+//! and detector parameters, family names, [DetectionData] and pose results.
 
+#[cfg(feature = "calibration")]
+pub mod calibration;
+pub mod custom_family;
 pub mod detection;
 pub mod detector;
+pub mod detector_pool;
 pub mod error;
 pub mod families;
+pub mod generator;
 pub mod image_buf;
 pub mod matd;
 pub mod pose;
 pub mod zarray;
 
-pub use detection::Detection;
+pub use custom_family::{CustomFamily, FamilyData};
+pub use detection::{Detection, DetectionData};
 pub use detector::{Detector, DetectorBuilder};
+pub use detector_pool::{DetectorPool, PooledDetector};
 pub use error::Error;
 pub use families::Family;
+pub use generator::{FamilyGenerator, Layout};
 pub use image_buf::Image;
 pub use matd::MatdRef;
-pub use pose::{Pose, PoseEstimation, TagParams};
+pub use pose::{Pose, PoseEstimation, TagParams, TagSize};
 pub use zarray::ZArray;
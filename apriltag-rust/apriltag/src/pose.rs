@@ -1,16 +1,20 @@
 //! Pose types storing estimated rotation and translation parameters.
 
-use crate::MatdRef;
+use crate::{Detection, Family, MatdRef};
 use apriltag_sys as sys;
 use std::fmt::{self, Debug, Formatter};
 
 /// Estimated pose along with error.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PoseEstimation {
     pub pose: Pose,
     pub error: f64,
 }
 
 /// Estimated pose rotation and translation parameters.
+///
+/// With the `serde` feature, it is serialized as a `rotation` matrix in rows
+/// and a `translation` vector.
 #[repr(transparent)]
 pub struct Pose(pub(crate) sys::apriltag_pose_t);
 
@@ -36,6 +40,71 @@ impl Debug for Pose {
     }
 }
 
+// SAFETY: The pose exclusively owns its matrices.
+unsafe impl Send for Pose {}
+unsafe impl Sync for Pose {}
+
+#[cfg(feature = "serde")]
+impl Pose {
+    /// Creates a pose from a rotation matrix in rows and a translation vector.
+    fn from_arrays(rotation: &[[f64; 3]; 3], translation: &[f64; 3]) -> Option<Self> {
+        let rotation = rotation.concat();
+        unsafe {
+            let r = sys::matd_create_data(3, 3, rotation.as_ptr());
+            let t = sys::matd_create_data(3, 1, translation.as_ptr());
+            if r.is_null() || t.is_null() {
+                sys::matd_destroy(r);
+                sys::matd_destroy(t);
+                return None;
+            }
+            Some(Self(sys::apriltag_pose_t { R: r, t }))
+        }
+    }
+}
+
+/// The serialized form of a [Pose].
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PoseRepr {
+    rotation: [[f64; 3]; 3],
+    translation: [f64; 3],
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Pose {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let rotation = self.rotation();
+        let translation = self.translation();
+        let rotation = rotation.data();
+        let translation = translation.data();
+        let repr = PoseRepr {
+            rotation: [0, 1, 2].map(|row| [0, 1, 2].map(|col| rotation[row * 3 + col])),
+            translation: [translation[0], translation[1], translation[2]],
+        };
+        repr.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Pose {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let PoseRepr {
+            rotation,
+            translation,
+        } = PoseRepr::deserialize(deserializer)?;
+        Self::from_arrays(&rotation, &translation)
+            .ok_or_else(|| D::Error::custom("failed to allocate the pose matrices"))
+    }
+}
+
 impl Drop for Pose {
     fn drop(&mut self) {
         unsafe {
@@ -47,10 +116,90 @@ impl Drop for Pose {
 
 /// Stores tag size and camera parameters.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TagParams {
+    /// The width of the border square the detector locates, see [TagSize::Border].
     pub tagsize: f64,
     pub fx: f64,
     pub fy: f64,
     pub cx: f64,
     pub cy: f64,
 }
+
+impl TagParams {
+    /// Creates parameters for tags of the family with the given size, which is
+    /// converted to the border width pose estimation expects.
+    pub fn new(size: TagSize, family: &Family, fx: f64, fy: f64, cx: f64, cy: f64) -> Self {
+        Self {
+            tagsize: size.border_width(family),
+            fx,
+            fy,
+            cx,
+            cy,
+        }
+    }
+
+    /// Creates parameters for the tag of the detection with the given size,
+    /// converted using the geometry of the detected family.
+    pub fn for_detection(
+        size: TagSize,
+        detection: &Detection,
+        fx: f64,
+        fy: f64,
+        cx: f64,
+        cy: f64,
+    ) -> Self {
+        Self {
+            tagsize: size.detection_border_width(detection),
+            fx,
+            fy,
+            cx,
+            cy,
+        }
+    }
+}
+
+/// The physical width of a tag in one of the two senses it is measured in.
+///
+/// The AprilTag library's tag size is the width of the border square the
+/// detector locates. For the classic families, such as tag36h11, that is the
+/// outermost black square of the printed tag. The standard and circle families
+/// however print data bits outside their border square, so their printed
+/// width is larger than the tag size, e.g. 9/5 of it for tagStandard41h12.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TagSize {
+    /// The width in meters of the border square the detector locates.
+    Border(f64),
+    /// The outer width in meters of the printed black pixels, i.e. the black
+    /// square of the classic families and the whole tag of the standard and
+    /// circle families.
+    Printed(f64),
+}
+
+impl TagSize {
+    /// Converts the size to the width of the border square of the family's tags.
+    pub fn border_width(self, family: &Family) -> f64 {
+        self.border_width_raw(family.raw())
+    }
+
+    /// Converts the size to the width of the border square of the detected tag.
+    pub fn detection_border_width(self, detection: &Detection) -> f64 {
+        self.border_width_raw(detection.family_raw())
+    }
+
+    fn border_width_raw(self, family: &sys::apriltag_family_t) -> f64 {
+        match self {
+            Self::Border(width) => width,
+            // The black square of the classic families is their border square
+            Self::Printed(width) if !family.reversed_border => width,
+            Self::Printed(width) => {
+                width * family.width_at_border as f64 / family.total_width as f64
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "calibration")]
+
+use apriltag::calibration::{CameraCalibration, Distortion};
+
+#[test]
+fn opencv_yaml_calibration() {
+    let text = r#"%YAML:1.0
+---
+image_width: 1280
+image_height: 720
+camera_matrix: !!opencv-matrix
+   rows: 3
+   cols: 3
+   dt: d
+   data: [ 9.0e+02, 0., 6.5e+02, 0., 8.8e+02, 3.3e+02, 0., 0., 1. ]
+distortion_coefficients: !!opencv-matrix
+   rows: 1
+   cols: 5
+   dt: d
+   data: [ -0.2, 0.05, 0.001, -0.002, 0.01 ]
+"#;
+    let calibration = CameraCalibration::from_opencv_yaml(text).unwrap();
+    assert_eq!(calibration.image_width, Some(1280));
+    assert_eq!((calibration.fx, calibration.fy), (900.0, 880.0));
+    assert_eq!((calibration.cx, calibration.cy), (650.0, 330.0));
+    assert_eq!(
+        calibration.distortion,
+        Distortion::BrownConrady {
+            k1: -0.2,
+            k2: 0.05,
+            k3: 0.01,
+            p1: 0.001,
+            p2: -0.002,
+        }
+    );
+
+    let params = calibration.tag_params(0.1);
+    assert_eq!(params.tagsize, 0.1);
+    assert_eq!(params.fx, 900.0);
+}
+
+#[test]
+fn ros_camera_info_calibration() {
+    let text = r#"image_width: 640
+image_height: 480
+camera_name: narrow_stereo
+camera_matrix:
+  rows: 3
+  cols: 3
+  data: [500.0, 0.0, 320.0, 0.0, 510.0, 240.0, 0.0, 0.0, 1.0]
+distortion_model: equidistant
+distortion_coefficients:
+  rows: 1
+  cols: 4
+  data: [0.1, -0.01, 0.002, -0.0003]
+rectification_matrix:
+  rows: 3
+  cols: 3
+  data: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+projection_matrix:
+  rows: 3
+  cols: 4
+  data: [500.0, 0.0, 320.0, 0.0, 0.0, 510.0, 240.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+"#;
+    let calibration = CameraCalibration::from_ros_camera_info(text).unwrap();
+    assert_eq!((calibration.fx, calibration.fy), (500.0, 510.0));
+    assert_eq!(
+        calibration.distortion,
+        Distortion::KannalaBrandt {
+            k1: 0.1,
+            k2: -0.01,
+            k3: 0.002,
+            k4: -0.0003,
+        }
+    );
+}
+
+#[test]
+fn json_calibration() {
+    let text = r#"{
+  "image_width": 1280,
+  "image_height": 720,
+  "camera_index": 0,
+  "timestamp": "2025-01-01T00:00:00Z",
+  "model": "pinhole",
+  "fx": 900.0,
+  "fy": 900.0,
+  "cx": 640.0,
+  "cy": 360.0,
+  "skew": 0.0,
+  "distortion": [],
+  "rms_error": null
+}"#;
+    let calibration = CameraCalibration::from_json(text).unwrap();
+    assert_eq!(calibration.distortion, Distortion::None);
+    assert_eq!(calibration.cx, 640.0);
+    assert!(CameraCalibration::from_json("{}").is_err());
+}
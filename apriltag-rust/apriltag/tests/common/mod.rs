@@ -0,0 +1,44 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use apriltag::{Detection, DetectorBuilder, Family, Image};
+
+/// Renders a tag scaled up and padded with white so that the detector finds it.
+pub fn render_scene(family: &Family, id: usize) -> Image {
+    let tag = family.tag_image(id).unwrap();
+    let (scale, padding) = (10, 20);
+    let size = tag.width() * scale + 2 * padding;
+    let mut image = Image::zeros_with_alignment(size, size, 96).unwrap();
+    for y in 0..size {
+        for x in 0..size {
+            let (tag_x, tag_y) = (
+                x.wrapping_sub(padding) / scale,
+                y.wrapping_sub(padding) / scale,
+            );
+            image[(x, y)] = if tag_x < tag.width() && tag_y < tag.height() {
+                tag[(tag_x, tag_y)]
+            } else {
+                255
+            };
+        }
+    }
+    image
+}
+
+/// Loads the photo of the tag16h5 tags 2, 12, 22 and 29.
+pub fn pnm_image() -> Image {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/test_data/DICT_APRILTAG_16h5-2x2-500-10-0.8-29,12,22,2.pnm"
+    );
+    Image::from_pnm_file(path).unwrap()
+}
+
+/// Detects the tags in [pnm_image].
+pub fn pnm_detections() -> Vec<Detection> {
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_16h5(), 1)
+        .build()
+        .expect("Valid builder");
+    detector.detect(&pnm_image())
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use apriltag::{DetectorBuilder, Family, FamilyData};
+
+#[test]
+fn custom_family_detection() {
+    let mut data = Family::tag_16h5().data();
+    data.name = "myTag16h5".to_string();
+    data.codes.truncate(10);
+
+    let family = Family::custom(data.clone()).unwrap();
+    assert_eq!(family.name(), "myTag16h5");
+    assert_eq!(family.tag_count(), 10);
+    assert_eq!(family.data(), data);
+
+    let image = common::render_scene(&family, 7);
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(family, 1)
+        .build()
+        .expect("Valid builder");
+    let detections = detector.detect(&image);
+    assert_eq!(detections.len(), 1);
+    assert_eq!(detections[0].id(), 7);
+    assert_eq!(detections[0].family_name(), "myTag16h5");
+}
+
+#[test]
+fn c_source_round_trip() {
+    for family in [Family::tag_36h11(), Family::tag_standard_41h12()] {
+        let data = family.data();
+        assert_eq!(
+            FamilyData::from_c_source(&data.to_c_source()).unwrap(),
+            data
+        );
+    }
+}
+
+#[test]
+fn to_c_source_round_trip() {
+    for family in [Family::tag_16h5(), Family::tag_circle_21h7()] {
+        let data = family.data();
+        let source = data.to_c_source();
+        assert!(source.contains("#include <string.h>"));
+        assert!(source.contains(&format!("apriltag_family_t *{}_create()", data.name)));
+        assert_eq!(FamilyData::from_c_source(&source).unwrap(), data);
+    }
+}
+
+#[test]
+fn c_source_with_indexed_codes() {
+    let source = r#"
+        tf->name = strdup("tiny");
+        tf->h = 1; tf->ncodes = 2; tf->nbits = 1;
+        tf->codes[0] = 0x0UL; // black
+        tf->codes[1] = 0x1UL; // white
+        tf->bit_x[0] = 1; tf->bit_y[0] = 1;
+        tf->width_at_border = 3; tf->total_width = 5; tf->reversed_border = false;
+    "#;
+    let data = FamilyData::from_c_source(source).unwrap();
+    assert_eq!(data.codes, [0, 1]);
+    assert!(Family::custom(data).is_ok());
+}
+
+#[test]
+fn invalid_custom_families() {
+    let valid = Family::tag_16h5().data();
+
+    let mut data = valid.clone();
+    data.bit_y.pop();
+    assert!(Family::custom(data).is_err());
+
+    let mut data = valid.clone();
+    data.codes.push(1 << 20);
+    assert!(Family::custom(data).is_err());
+
+    let mut data = valid;
+    data.bit_x[0] = 100;
+    assert!(Family::custom(data).is_err());
+
+    assert!(FamilyData::from_c_source("tf->name = strdup(\"broken\");").is_err());
+}
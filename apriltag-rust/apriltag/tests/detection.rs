@@ -1,4 +1,9 @@
-use apriltag::{DetectorBuilder, Family, Image};
+mod common;
+
+use apriltag::{
+    detector::QuadThresholds, DetectionData, Detector, DetectorBuilder, Family, Image, TagParams,
+};
+use measurements::angle::Angle;
 
 #[test]
 fn pnm_file_detection() {
@@ -13,12 +18,96 @@ fn pnm_file_detection() {
         .build()
         .expect("Valid builder");
 
+    let detections = detector.detect(&image);
+    assert!(detections
+        .iter()
+        .all(|detection| detection.family_name() == "tag16h5"));
+
     // Ensure correct parsing of IDs
-    let mut ids_found: Vec<_> = detector
-        .detect(&image)
+    let mut ids_found: Vec<_> = detections
         .into_iter()
         .map(|detection| detection.id())
         .collect();
     ids_found.sort_unstable();
     assert_eq!(ids_found, [2, 12, 22, 29]);
 }
+
+#[test]
+fn thresholds_round_trip() {
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_16h5(), 1)
+        .build()
+        .expect("Valid builder");
+
+    // The library defaults to a critical angle of 10 degrees
+    let mut thresholds = detector.thresholds();
+    assert!((thresholds.min_angle.as_degrees() - 10.0).abs() < 1e-4);
+    detector.set_thresholds(thresholds);
+    assert_eq!(detector.thresholds(), thresholds);
+
+    thresholds.min_cluster_pixels += 10;
+    thresholds.min_angle = Angle::from_degrees(20.0);
+    thresholds.deglitch = !thresholds.deglitch;
+    detector.set_thresholds(thresholds);
+    let parsed = detector.thresholds();
+    assert!((parsed.min_angle.as_degrees() - 20.0).abs() < 1e-4);
+    assert_eq!(
+        QuadThresholds {
+            min_angle: thresholds.min_angle,
+            ..parsed
+        },
+        thresholds
+    );
+
+    // The quad fitter only reads the cosine of the angle
+    let ptr = detector.into_raw();
+    let params = unsafe { ptr.as_ref().qtp };
+    drop(unsafe { Detector::from_raw(ptr.as_ptr()) });
+    assert_eq!(params.cos_critical_rad, 20f64.to_radians().cos() as f32);
+    assert_eq!(params.critical_rad, 20f64.to_radians() as f32);
+}
+
+#[test]
+fn detection_data_matches_detection() {
+    for detection in common::pnm_detections() {
+        let data = detection.to_data();
+        assert_eq!(data.id, detection.id());
+        assert_eq!(data.family, "tag16h5");
+        assert_eq!(data.hamming, detection.hamming());
+        assert_eq!(data.decision_margin, detection.decision_margin());
+        assert_eq!(data.center, detection.center());
+        assert_eq!(data.corners, detection.corners());
+        assert_eq!(data.homography.concat(), detection.homography().data());
+    }
+}
+
+#[test]
+fn detection_data_pose_estimation() {
+    let params = TagParams {
+        tagsize: 0.1,
+        fx: 500.0,
+        fy: 500.0,
+        cx: 250.0,
+        cy: 250.0,
+    };
+    for detection in common::pnm_detections() {
+        let data = DetectionData::from(&detection);
+        let expected = detection.estimate_tag_pose(&params).unwrap();
+        let pose = data.estimate_tag_pose(&params).unwrap();
+        assert_eq!(pose.rotation().data(), expected.rotation().data());
+        assert_eq!(pose.translation().data(), expected.translation().data());
+
+        let poses = data.estimate_tag_pose_orthogonal_iteration(&params, 50);
+        assert!(!poses.is_empty());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn detection_data_serde_round_trip() {
+    for detection in common::pnm_detections() {
+        let data = detection.to_data();
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(serde_json::from_str::<DetectionData>(&json).unwrap(), data);
+    }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use apriltag::{DetectorBuilder, Family};
+
+#[test]
+fn tag_image_round_trip() {
+    let family = Family::tag_36h11();
+    assert_eq!(family.total_width(), 10);
+    assert_eq!(family.width_at_border(), 8);
+
+    let image = common::render_scene(&family, 42);
+
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_36h11(), 1)
+        .build()
+        .expect("Valid builder");
+    let ids: Vec<_> = detector
+        .detect(&image)
+        .into_iter()
+        .map(|detection| detection.id())
+        .collect();
+    assert_eq!(ids, [42]);
+}
+
+#[test]
+fn tag_image_out_of_range() {
+    assert!(Family::tag_16h5().tag_image(1000).is_err());
+}
+
+#[test]
+fn code_tables_match_tag_image() {
+    for family in [Family::tag_36h11(), Family::tag_standard_41h12()] {
+        assert_eq!(family.bit_x().len(), family.nbits());
+        assert_eq!(family.bit_y().len(), family.nbits());
+
+        let id = family.codes().len() - 1;
+        let code = family.codes()[id];
+        let tag = family.tag_image(id).unwrap();
+        let offset = ((family.total_width() - family.width_at_border()) / 2) as i32;
+        for (bit, (&x, &y)) in family.bit_x().iter().zip(family.bit_y()).enumerate() {
+            let black = code & (1 << (family.nbits() - bit - 1)) == 0;
+            let pixel = tag[((x + offset) as usize, (y + offset) as usize)];
+            assert_eq!(pixel == 0, black, "bit {bit} of tag {id}");
+        }
+    }
+}
+
+#[test]
+fn code_table_layout() {
+    let family = Family::tag_36h11();
+    assert_eq!(family.codes().len(), family.tag_count());
+    assert_eq!(family.nbits(), 36);
+    assert!(!family.reversed_border());
+
+    let family = Family::tag_standard_41h12();
+    assert_eq!(family.nbits(), 41);
+    assert!(family.reversed_border());
+    assert!(family.bit_x().iter().any(|&x| x < 0));
+}
+
+#[test]
+fn family_metadata() {
+    let family = Family::tag_36h11();
+    assert_eq!(family.name(), "tag36h11");
+    assert_eq!(family.tag_count(), 587);
+    assert_eq!(family.min_hamming(), 11);
+    assert_eq!(family.border_ratio(), 0.8);
+
+    let family: Family = "tagStandard41h12".parse().unwrap();
+    assert_eq!(family.name(), "tagStandard41h12");
+    assert_eq!(family.min_hamming(), 12);
+    assert_eq!(family.border_ratio(), 5.0 / 9.0);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use apriltag::{DetectorBuilder, Family, FamilyGenerator, Layout};
+
+#[test]
+fn classic_layout_matches_tag36h11() {
+    let layout = Layout::classic(8).unwrap();
+    let family = Family::tag_36h11();
+    assert_eq!(layout.nbits(), family.nbits());
+    assert_eq!(layout.total_width(), family.total_width());
+    assert_eq!(layout.reversed_border(), family.reversed_border());
+    assert_eq!(layout.bit_x(), family.bit_x());
+    assert_eq!(layout.bit_y(), family.bit_y());
+}
+
+#[test]
+fn standard_layout_geometry() {
+    let layout = Layout::standard(9).unwrap();
+    let family = Family::tag_standard_41h12();
+    assert_eq!(layout.nbits(), family.nbits());
+    assert_eq!(layout.width_at_border(), family.width_at_border());
+    assert_eq!(layout.total_width(), family.total_width());
+    assert!(layout.reversed_border());
+}
+
+#[test]
+fn parsed_layout() {
+    let layout = Layout::parse("wwwww/wbbbw/wbdbw/wbbbw/wwwww").unwrap();
+    assert_eq!(layout.nbits(), 1);
+    assert_eq!(layout.width_at_border(), 3);
+    assert_eq!((layout.bit_x(), layout.bit_y()), (&[1][..], &[1][..]));
+
+    assert!(Layout::parse("wwww/wbbw/wbdw/wwww").is_err());
+    assert!(Layout::parse("ddd/ddd/ddd").is_err());
+}
+
+#[test]
+fn rotation_cycles_quarters() {
+    let layout = Layout::classic(5).unwrap();
+    assert_eq!(layout.nbits(), 9);
+    let code = 0b1_0000_0001;
+    let mut rotated = code;
+    for _ in 0..4 {
+        rotated = layout.rotate90(rotated);
+    }
+    assert_eq!(rotated, code);
+    assert_ne!(layout.rotate90(code), code);
+}
+
+#[test]
+fn generated_family() {
+    let generator = FamilyGenerator::new(Layout::classic(6).unwrap(), 5).min_complexity(5);
+    let data = generator.generate("tagGenerated16h5").unwrap();
+    assert_eq!(generator.generate("tagGenerated16h5").unwrap(), data);
+    assert!(data.codes.len() > 10);
+
+    // Every code is far from all rotations of every code, including itself
+    let layout = Layout::classic(6).unwrap();
+    for (index, &code) in data.codes.iter().enumerate() {
+        for (other_index, &other) in data.codes.iter().enumerate() {
+            let mut rotated = other;
+            for turn in 0..4 {
+                if index != other_index || turn != 0 {
+                    assert!((code ^ rotated).count_ones() >= 5);
+                }
+                rotated = layout.rotate90(rotated);
+            }
+        }
+    }
+
+    // The generated tags render and detect like the built-in ones
+    let family = Family::custom(data).unwrap();
+    let image = common::render_scene(&family, 3);
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(family, 1)
+        .build()
+        .expect("Valid builder");
+    let ids: Vec<_> = detector
+        .detect(&image)
+        .into_iter()
+        .map(|detection| detection.id())
+        .collect();
+    assert_eq!(ids, [3]);
+}
+
+#[test]
+fn capped_generation() {
+    let data = FamilyGenerator::new(Layout::standard(9).unwrap(), 12)
+        .max_codes(3)
+        .generate("tagGenerated41h12")
+        .unwrap();
+    assert_eq!(data.codes.len(), 3);
+    assert!(Family::custom(data).is_ok());
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use apriltag::{DetectorBuilder, Family, TagParams, TagSize};
+
+#[test]
+fn printed_size_of_classic_family_is_border_size() {
+    let family = Family::tag_36h11();
+    assert_eq!(TagSize::Printed(0.1).border_width(&family), 0.1);
+    assert_eq!(TagSize::Border(0.1).border_width(&family), 0.1);
+}
+
+#[test]
+fn printed_size_of_standard_family() {
+    let family = Family::tag_standard_41h12();
+    assert!((TagSize::Printed(0.09).border_width(&family) - 0.05).abs() < 1e-12);
+    assert_eq!(TagSize::Border(0.05).border_width(&family), 0.05);
+
+    let params = TagParams::new(TagSize::Printed(0.09), &family, 600.0, 600.0, 320.0, 240.0);
+    assert!((params.tagsize - 0.05).abs() < 1e-12);
+}
+
+#[test]
+fn printed_size_of_detected_tag() {
+    let family = Family::tag_standard_41h12();
+    let image = common::render_scene(&family, 7);
+
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(family, 1)
+        .build()
+        .expect("Valid builder");
+    let detections = detector.detect(&image);
+    assert_eq!(detections.len(), 1);
+
+    let params = TagParams::for_detection(
+        TagSize::Printed(0.09),
+        &detections[0],
+        600.0,
+        600.0,
+        320.0,
+        240.0,
+    );
+    assert!((params.tagsize - 0.05).abs() < 1e-12);
+}
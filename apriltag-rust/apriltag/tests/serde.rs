@@ -0,0 +1,103 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use apriltag::{
+    detector::QuadThresholds, Detector, DetectorBuilder, Family, PoseEstimation, TagParams, TagSize,
+};
+
+#[test]
+fn tag_params_serde_round_trip() {
+    let params = TagParams::new(
+        TagSize::Printed(0.1),
+        &Family::tag_36h11(),
+        600.0,
+        610.0,
+        320.0,
+        240.0,
+    );
+    let json = serde_json::to_string(&params).unwrap();
+    let parsed: TagParams = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        (parsed.tagsize, parsed.fx, parsed.fy, parsed.cx, parsed.cy),
+        (params.tagsize, params.fx, params.fy, params.cx, params.cy)
+    );
+
+    let size: TagSize = serde_json::from_str(r#"{"printed":0.09}"#).unwrap();
+    assert_eq!(size, TagSize::Printed(0.09));
+}
+
+#[test]
+fn quad_thresholds_serde_round_trip() {
+    let detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_36h11(), 1)
+        .build()
+        .expect("Valid builder");
+    let thresholds = detector.thresholds();
+    let json = serde_json::to_string(&thresholds).unwrap();
+    assert_eq!(
+        serde_json::from_str::<QuadThresholds>(&json).unwrap(),
+        thresholds
+    );
+
+    // The default critical angle of 10 degrees is serialized in radians
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let min_angle = value["min_angle"].as_f64().unwrap();
+    assert!((min_angle - 10f64.to_radians()).abs() < 1e-6);
+}
+
+#[test]
+fn quad_thresholds_deserialize_to_critical_cosine() {
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_36h11(), 1)
+        .build()
+        .expect("Valid builder");
+    let mut value = serde_json::to_value(detector.thresholds()).unwrap();
+    value["min_angle"] = 0.3.into();
+    detector.set_thresholds(serde_json::from_value(value).unwrap());
+
+    // The quad fitter only reads the cosine of the angle
+    let ptr = detector.into_raw();
+    let params = unsafe { ptr.as_ref().qtp };
+    drop(unsafe { Detector::from_raw(ptr.as_ptr()) });
+    assert_eq!(params.cos_critical_rad, 0.3f64.cos() as f32);
+}
+
+#[test]
+fn family_serializes_as_name() {
+    let json = serde_json::to_string(&Family::tag_standard_41h12()).unwrap();
+    assert_eq!(json, r#""tagStandard41h12""#);
+    let family: Family = serde_json::from_str(&json).unwrap();
+    assert_eq!(family.name(), "tagStandard41h12");
+    assert!(serde_json::from_str::<Family>(r#""tag99h99""#).is_err());
+}
+
+#[test]
+fn pose_serde_round_trip() {
+    let params = TagParams::new(
+        TagSize::Border(0.1),
+        &Family::tag_16h5(),
+        600.0,
+        600.0,
+        250.0,
+        250.0,
+    );
+    for data in common::pnm_detections()
+        .iter()
+        .map(|detection| detection.to_data())
+    {
+        for estimation in data.estimate_tag_pose_orthogonal_iteration(&params, 50) {
+            let json = serde_json::to_string(&estimation).unwrap();
+            let parsed: PoseEstimation = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.error, estimation.error);
+            assert_eq!(
+                parsed.pose.rotation().data(),
+                estimation.pose.rotation().data()
+            );
+            assert_eq!(
+                parsed.pose.translation().data(),
+                estimation.pose.translation().data()
+            );
+        }
+    }
+}
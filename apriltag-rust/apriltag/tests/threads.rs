@@ -0,0 +1,79 @@
+mod common;
+
+use apriltag::{Detection, Detector, DetectorBuilder, DetectorPool, Family, Image, Pose};
+use std::{sync::mpsc, thread};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn detector() -> Result<Detector, apriltag::Error> {
+    DetectorBuilder::new()
+        .add_family_bits(Family::tag_16h5(), 1)
+        .build()
+}
+
+fn sorted_ids(detections: &[Detection]) -> Vec<usize> {
+    let mut ids: Vec<_> = detections.iter().map(|detection| detection.id()).collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn types_are_send_and_sync() {
+    assert_send_sync::<Detector>();
+    assert_send_sync::<Detection>();
+    assert_send_sync::<Image>();
+    assert_send_sync::<Pose>();
+    assert_send_sync::<DetectorPool>();
+}
+
+#[test]
+fn detector_in_worker_thread() {
+    let mut detector = detector().unwrap();
+    let image = common::pnm_image();
+
+    // Detect in a worker thread and send the detections back over a channel
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        for detection in detector.detect(&image) {
+            sender.send(detection).unwrap();
+        }
+        detector
+    });
+    let detections: Vec<_> = receiver.iter().collect();
+    let mut detector = worker.join().unwrap();
+
+    assert_eq!(sorted_ids(&detections), [2, 12, 22, 29]);
+    assert_eq!(
+        sorted_ids(&detector.detect(&common::pnm_image())),
+        [2, 12, 22, 29]
+    );
+}
+
+#[test]
+fn concurrent_pool_detection() {
+    let pool = DetectorPool::new(2, detector).unwrap();
+    assert_eq!(pool.size(), 2);
+    let image = common::pnm_image();
+
+    let results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| (0..3).map(|_| pool.detect(&image)).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    assert_eq!(results.len(), 12);
+    for detections in &results {
+        assert_eq!(sorted_ids(detections), [2, 12, 22, 29]);
+    }
+
+    pool.for_each(|detector| detector.set_decimation(2.0));
+    assert_eq!(sorted_ids(&pool.get().detect(&image)), [2, 12, 22, 29]);
+}
+
+#[test]
+fn empty_pool() {
+    assert!(DetectorPool::new(0, detector).is_err());
+}
@@ -0,0 +1,256 @@
+//! Averaging of the detections of one tag over several frames.
+
+use apriltag::{detection::TAG_CORNERS, Detection};
+use apriltag_nalgebra::{nalgebra::Point3, PoseExt};
+
+use crate::camera_model::CameraModel;
+
+/// Frames whose reprojection error exceeds this multiple of the median error
+/// are rejected as outliers.
+const OUTLIER_FACTOR: f64 = 3.0;
+
+/// The deviation in pixels below which a frame is never rejected, so that a
+/// perfectly still tag does not turn sub-pixel noise into outliers.
+const MIN_OUTLIER_DEVIATION: f64 = 0.5;
+
+/// A tag detection averaged over the frames of one capture position.
+pub struct AveragedDetection {
+    /// A detection with the mean corners of the inlier frames.
+    pub detection: Detection,
+    /// The detections of the inlier frames.
+    pub frames: Vec<Detection>,
+    /// The number of frames rejected as outliers.
+    pub rejected: usize,
+    /// The RMS distance in pixels of the inlier corners from their mean.
+    pub corner_spread: f64,
+}
+
+impl AveragedDetection {
+    /// Gets the standard error in pixels of the mean corner positions.
+    pub fn corner_standard_error(&self) -> f64 {
+        self.corner_spread / (self.frames.len() as f64).sqrt()
+    }
+}
+
+/// Averages the corners of detections of the same tag in several frames.
+///
+/// The consensus pose of the tag is estimated with the camera model from the
+/// per-coordinate median corners of the frames. Each frame's reprojection
+/// error is the RMS distance of its corners from the tag corners projected
+/// with the consensus pose. Frames with outlying errors are rejected and the
+/// corners of the rest are averaged.
+/// Returns `None` if there are no detections, the consensus pose cannot be
+/// estimated or the averaged corners are degenerate.
+pub fn average_detections(
+    detections: Vec<Detection>,
+    model: &CameraModel,
+) -> Option<AveragedDetection> {
+    let reprojected = reproject_consensus(&detections, model)?;
+    let deviations: Vec<f64> = detections
+        .iter()
+        .map(|detection| rms_distance(&detection.corners(), &reprojected))
+        .collect();
+    let threshold = (OUTLIER_FACTOR * median(deviations.clone())).max(MIN_OUTLIER_DEVIATION);
+
+    let total = detections.len();
+    let frames: Vec<Detection> = detections
+        .into_iter()
+        .zip(&deviations)
+        .filter(|(_, &deviation)| deviation <= threshold)
+        .map(|(detection, _)| detection)
+        .collect();
+
+    let mut mean = [[0.0; 2]; 4];
+    for detection in &frames {
+        for (sum, corner) in mean.iter_mut().zip(detection.corners()) {
+            sum[0] += corner[0] / frames.len() as f64;
+            sum[1] += corner[1] / frames.len() as f64;
+        }
+    }
+    let corner_spread = if frames.len() > 1 {
+        let squared: f64 = frames
+            .iter()
+            .map(|detection| rms_distance(&detection.corners(), &mean).powi(2))
+            .sum();
+        (squared / (frames.len() - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+
+    Some(AveragedDetection {
+        detection: frames[0].with_corners(mean)?,
+        rejected: total - frames.len(),
+        frames,
+        corner_spread,
+    })
+}
+
+/// Projects the tag corners with the pose of the median corners.
+///
+/// The reprojection does not depend on the tag size, which scales the tag
+/// and the estimated translation alike, so a unit tag is used.
+fn reproject_consensus(detections: &[Detection], model: &CameraModel) -> Option<[[f64; 2]; 4]> {
+    let consensus = detections
+        .first()?
+        .with_corners(corner_medians(detections))?;
+    let pose = model.estimate_tag_pose(&consensus, 1.0)?.to_na();
+    let mut reprojected = [[0.0; 2]; 4];
+    for (pixel, [x, y]) in reprojected.iter_mut().zip(TAG_CORNERS) {
+        *pixel = model.project(&(pose * Point3::new(x / 2.0, y / 2.0, 0.0)))?;
+    }
+    Some(reprojected)
+}
+
+fn corner_medians(detections: &[Detection]) -> [[f64; 2]; 4] {
+    let mut medians = [[0.0; 2]; 4];
+    for (corner, median_corner) in medians.iter_mut().enumerate() {
+        for (axis, value) in median_corner.iter_mut().enumerate() {
+            *value = median(
+                detections
+                    .iter()
+                    .map(|detection| detection.corners()[corner][axis])
+                    .collect(),
+            );
+        }
+    }
+    medians
+}
+
+fn rms_distance(corners: &[[f64; 2]; 4], reference: &[[f64; 2]; 4]) -> f64 {
+    let squared: f64 = corners
+        .iter()
+        .zip(reference)
+        .map(|(corner, reference)| {
+            (corner[0] - reference[0]).powi(2) + (corner[1] - reference[1]).powi(2)
+        })
+        .sum();
+    (squared / 4.0).sqrt()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        intrinsics::Intrinsics,
+        synthetic::{template_detection, IMAGE_HEIGHT, IMAGE_WIDTH},
+    };
+
+    const CORNERS: [[f64; 2]; 4] = [
+        [100.0, 200.0],
+        [200.0, 200.0],
+        [200.0, 100.0],
+        [100.0, 100.0],
+    ];
+
+    fn camera() -> CameraModel {
+        CameraModel::Pinhole(Intrinsics::centered(IMAGE_WIDTH, IMAGE_HEIGHT, 800.0))
+    }
+
+    /// Creates a detection with the corners shifted by the offsets.
+    fn shifted(template: &Detection, [dx, dy]: [f64; 2]) -> Detection {
+        template
+            .with_corners(CORNERS.map(|[x, y]| [x + dx, y + dy]))
+            .unwrap()
+    }
+
+    fn assert_corners_near(corners: [[f64; 2]; 4], expected: [[f64; 2]; 4]) {
+        for (corner, expected) in corners.iter().zip(&expected) {
+            assert!((corner[0] - expected[0]).abs() < 1e-9, "{corners:?}");
+            assert!((corner[1] - expected[1]).abs() < 1e-9, "{corners:?}");
+        }
+    }
+
+    #[test]
+    fn averages_jittered_frames() {
+        let template = template_detection();
+        let offsets = [[0.1, -0.1], [-0.1, 0.1], [0.1, 0.1], [-0.1, -0.1]];
+        let detections = offsets
+            .iter()
+            .map(|&offset| shifted(&template, offset))
+            .collect();
+
+        let averaged = average_detections(detections, &camera()).unwrap();
+        assert_eq!(averaged.rejected, 0);
+        assert_eq!(averaged.frames.len(), 4);
+        assert_corners_near(averaged.detection.corners(), CORNERS);
+        // Every frame is 0.1·√2 from the mean, with Bessel's correction
+        let spread = (4.0 * 0.02 / 3.0_f64).sqrt();
+        assert!((averaged.corner_spread - spread).abs() < 1e-9);
+        assert!((averaged.corner_standard_error() - spread / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_outlier_frames() {
+        let template = template_detection();
+        let offsets = [[0.2, 0.0], [-0.2, 0.0], [0.0, 0.2], [0.0, -0.2], [5.0, 3.0]];
+        let detections = offsets
+            .iter()
+            .map(|&offset| shifted(&template, offset))
+            .collect();
+
+        let averaged = average_detections(detections, &camera()).unwrap();
+        assert_eq!(averaged.rejected, 1);
+        assert_eq!(averaged.frames.len(), 4);
+        assert_corners_near(averaged.detection.corners(), CORNERS);
+    }
+
+    #[test]
+    fn rejects_frames_that_do_not_fit_the_tag() {
+        let template = template_detection();
+        let mut detections: Vec<_> = [[0.2, 0.0], [-0.2, 0.0], [0.0, 0.2], [0.0, -0.2]]
+            .iter()
+            .map(|&offset| shifted(&template, offset))
+            .collect();
+        // A misdetected corner distorts the quad away from any tag pose
+        let mut corners = CORNERS;
+        corners[2][0] += 4.0;
+        detections.push(template.with_corners(corners).unwrap());
+
+        let averaged = average_detections(detections, &camera()).unwrap();
+        assert_eq!(averaged.rejected, 1);
+        assert_corners_near(averaged.detection.corners(), CORNERS);
+    }
+
+    #[test]
+    fn keeps_still_frames() {
+        let template = template_detection();
+        let offsets = [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.3, 0.0]];
+        let detections = offsets
+            .iter()
+            .map(|&offset| shifted(&template, offset))
+            .collect();
+
+        // The median deviation is zero, so the minimal threshold applies
+        let averaged = average_detections(detections, &camera()).unwrap();
+        assert_eq!(averaged.rejected, 0);
+        assert_corners_near(
+            averaged.detection.corners(),
+            CORNERS.map(|[x, y]| [x + 0.075, y]),
+        );
+    }
+
+    #[test]
+    fn single_frame() {
+        let averaged =
+            average_detections(vec![shifted(&template_detection(), [0.0, 0.0])], &camera())
+                .unwrap();
+        assert_eq!(averaged.rejected, 0);
+        assert_eq!(averaged.corner_spread, 0.0);
+        assert_corners_near(averaged.detection.corners(), CORNERS);
+    }
+
+    #[test]
+    fn no_average_without_detections() {
+        assert!(average_detections(vec![], &camera()).is_none());
+    }
+}
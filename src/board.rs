@@ -0,0 +1,434 @@
+//! Rigid boards of several tags used together as one calibration target.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{ensure, Context};
+use apriltag::{detection::TAG_CORNERS, Detection};
+use apriltag_nalgebra::nalgebra::{
+    Isometry3, Matrix3, Point3, Rotation3, Translation3, UnitQuaternion, Vector3,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calibration::{initial_pose, View},
+    intrinsics::Intrinsics,
+};
+
+/// A rigid board of tags with known corner positions in board coordinates.
+#[derive(Debug, Clone)]
+pub struct Board {
+    /// The corners of each tag in the order of [Detection::corners].
+    tags: BTreeMap<usize, [Point3<f64>; 4]>,
+}
+
+/// The layout of a board description file.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum BoardSpec {
+    Grid(GridSpec),
+    /// Tags with explicit corner positions.
+    Tags(Vec<TagSpec>),
+}
+
+/// A grid of equally sized tags, see [Board::grid].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GridSpec {
+    pub columns: usize,
+    pub rows: usize,
+    #[serde(default)]
+    pub first_id: usize,
+    /// The width in meters of the border square of each tag, which is the
+    /// printed `--tag-width` of the classic families but smaller than it for
+    /// the standard and circle families.
+    pub tag_size: f64,
+    /// The gap between neighbouring tags in meters.
+    pub spacing: f64,
+}
+
+impl GridSpec {
+    /// Creates the board described by the grid.
+    pub fn board(&self) -> Board {
+        Board::grid(
+            self.columns,
+            self.rows,
+            self.first_id,
+            self.tag_size,
+            self.spacing,
+        )
+    }
+
+    /// Serializes the grid as a board description file for [Board::load].
+    pub fn to_yaml(&self) -> serde_yaml::Result<String> {
+        // serde_yaml writes enums as tagged values, so spell out the `grid` key
+        #[derive(Serialize)]
+        struct GridFile<'a> {
+            grid: &'a GridSpec,
+        }
+
+        serde_yaml::to_string(&GridFile { grid: self })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagSpec {
+    id: usize,
+    corners: [[f64; 3]; 4],
+}
+
+impl Board {
+    /// Creates a grid of tags of the given width, separated by `spacing` meters.
+    ///
+    /// The board origin is the center of the first tag, with `x` along the rows
+    /// and `y` down the columns as seen with the tags upright, and the IDs
+    /// increase from `first_id` along the rows first.
+    pub fn grid(columns: usize, rows: usize, first_id: usize, tag_size: f64, spacing: f64) -> Self {
+        let pitch = tag_size + spacing;
+        let half_size = tag_size / 2.0;
+        let tags = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let (x, y) = (column as f64 * pitch, row as f64 * pitch);
+                let corners = TAG_CORNERS.map(|[corner_x, corner_y]| {
+                    Point3::new(x + corner_x * half_size, y + corner_y * half_size, 0.0)
+                });
+                (first_id + row * columns + column, corners)
+            })
+            .collect();
+        Self { tags }
+    }
+
+    /// Creates a board from the corners of each tag in board coordinates, in
+    /// the order of [Detection::corners].
+    pub fn from_tags(tags: BTreeMap<usize, [Point3<f64>; 4]>) -> Self {
+        Self { tags }
+    }
+
+    /// Loads a board description from a YAML or JSON file, see [from_yaml](Self::from_yaml).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read board '{}'", path.display()))?;
+        Self::from_yaml(&text).with_context(|| format!("invalid board '{}'", path.display()))
+    }
+
+    /// Parses a board description in YAML or JSON, holding either a `grid`
+    /// with `columns`, `rows`, `first_id`, `tag_size` and `spacing`, or a list
+    /// of `tags` with their `id` and four `corners`, e.g.
+    ///
+    /// ```yaml
+    /// grid:
+    ///   columns: 4
+    ///   rows: 3
+    ///   first_id: 0
+    ///   tag_size: 0.04
+    ///   spacing: 0.01
+    /// ```
+    ///
+    /// or
+    ///
+    /// ```yaml
+    /// tags:
+    ///   - id: 3
+    ///     corners: [[-0.02, 0.02, 0], [0.02, 0.02, 0], [0.02, -0.02, 0], [-0.02, -0.02, 0]]
+    /// ```
+    pub fn from_yaml(text: &str) -> anyhow::Result<Self> {
+        // serde_yaml reads enums from tagged values, so read the variant from the key
+        let spec: BoardSpec =
+            serde_yaml::with::singleton_map::deserialize(serde_yaml::Deserializer::from_str(text))?;
+
+        let board = match spec {
+            BoardSpec::Grid(grid) => {
+                ensure!(grid.tag_size > 0.0, "the tag size must be positive");
+                grid.board()
+            }
+            BoardSpec::Tags(tags) => {
+                let mut map = BTreeMap::new();
+                for TagSpec { id, corners } in tags {
+                    let corners = corners.map(|[x, y, z]| Point3::new(x, y, z));
+                    ensure!(map.insert(id, corners).is_none(), "duplicate tag {id}");
+                }
+                Self::from_tags(map)
+            }
+        };
+        ensure!(!board.tags.is_empty(), "the board has no tags");
+        Ok(board)
+    }
+
+    /// Gets the number of tags on the board.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Gets the corners of the tag with the given ID in board coordinates.
+    pub fn tag_corners(&self, id: usize) -> Option<&[Point3<f64>; 4]> {
+        self.tags.get(&id)
+    }
+
+    /// Collects the corners of every detected board tag into one view.
+    ///
+    /// The initial board pose is derived from the homography of the largest
+    /// detected tag and the given intrinsics. Detections of tags that are not
+    /// on the board are ignored.
+    /// Returns `None` if no board tag was detected or the pose is degenerate.
+    pub fn view(&self, detections: &[Detection], intrinsics: &Intrinsics) -> Option<View> {
+        let tags: Vec<_> = detections
+            .iter()
+            .filter_map(|detection| Some((detection, self.tag_corners(detection.id())?)))
+            .collect();
+        let correspondences = tags
+            .iter()
+            .flat_map(|(detection, corners)| corners.iter().copied().zip(detection.corners()))
+            .collect();
+
+        // Compose the pose of the largest tag with the tag's placement on the board
+        let (detection, corners) = tags
+            .iter()
+            .max_by(|(lhs, _), (rhs, _)| pixel_area(lhs).total_cmp(&pixel_area(rhs)))?;
+        let (tag_in_board, half_size) = tag_frame(corners)?;
+        let homography = Matrix3::from_row_slice(detection.homography().data());
+        let tag_in_camera =
+            initial_pose(&homography, &intrinsics.matrix().try_inverse()?, half_size)?;
+
+        Some(View {
+            correspondences,
+            initial_pose: tag_in_camera * tag_in_board.inverse(),
+        })
+    }
+}
+
+/// Computes the pose of a tag in board coordinates, in the frame where its
+/// corners are at `TAG_CORNERS` times its half size, along with the half size.
+fn tag_frame(corners: &[Point3<f64>; 4]) -> Option<(Isometry3<f64>, f64)> {
+    let x_axis: Vector3<f64> = corners[1] - corners[0];
+    let y_axis: Vector3<f64> = corners[0] - corners[3];
+    let half_size = x_axis.norm() / 2.0;
+    let rotation = Rotation3::from_matrix(&Matrix3::from_columns(&[
+        x_axis.try_normalize(1e-12)?,
+        y_axis.try_normalize(1e-12)?,
+        x_axis.cross(&y_axis).try_normalize(1e-12)?,
+    ]));
+    let center = corners
+        .iter()
+        .fold(Vector3::zeros(), |sum, corner| sum + corner.coords)
+        / 4.0;
+    Some((
+        Isometry3::from_parts(
+            Translation3::from(center),
+            UnitQuaternion::from_rotation_matrix(&rotation),
+        ),
+        half_size,
+    ))
+}
+
+/// Computes the area of a detected tag in square pixels.
+pub fn pixel_area(detection: &Detection) -> f64 {
+    let corners = detection.corners();
+    let twice_area: f64 = (0..4)
+        .map(|index| {
+            let ([x0, y0], [x1, y1]) = (corners[index], corners[(index + 1) % 4]);
+            x0 * y1 - x1 * y0
+        })
+        .sum();
+    twice_area.abs() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        calibration::{calibrate_views, refine_pose},
+        camera_model::CameraModel,
+        synthetic::{tag_detection, tag_pose, IMAGE_HEIGHT, IMAGE_WIDTH},
+    };
+
+    fn truth() -> CameraModel {
+        CameraModel::Pinhole(Intrinsics {
+            fx: 800.0,
+            fy: 810.0,
+            cx: 325.0,
+            cy: 235.0,
+            skew: 0.0,
+        })
+    }
+
+    /// A 3×2 grid of tags with IDs 1 to 6.
+    fn board() -> Board {
+        Board::grid(3, 2, 1, 0.05, 0.01)
+    }
+
+    /// Detects the tags with the given IDs on the board at the pose, and a tag
+    /// that is not on the board.
+    fn detect_board(model: &CameraModel, pose: &Isometry3<f64>, ids: &[usize]) -> Vec<Detection> {
+        let board = board();
+        let mut detections: Vec<_> = ids
+            .iter()
+            .map(|&id| {
+                let corners = board
+                    .tag_corners(id)
+                    .unwrap()
+                    .map(|corner| model.project(&(pose * corner)).unwrap());
+                tag_detection(id).with_corners(corners).unwrap()
+            })
+            .collect();
+        let stray =
+            tag_detection(0).with_corners([[10.0, 30.0], [30.0, 30.0], [30.0, 10.0], [10.0, 10.0]]);
+        detections.push(stray.unwrap());
+        detections
+    }
+
+    fn board_poses() -> Vec<Isometry3<f64>> {
+        vec![
+            tag_pose([0.0, 0.0, 0.0], [-0.06, -0.03, 0.4]),
+            tag_pose([0.4, 0.1, 0.0], [-0.08, -0.02, 0.45]),
+            tag_pose([-0.3, 0.3, 0.2], [-0.04, -0.05, 0.5]),
+            tag_pose([0.1, -0.4, -0.3], [-0.05, 0.0, 0.35]),
+            tag_pose([-0.2, -0.2, 1.0], [-0.02, -0.04, 0.55]),
+        ]
+    }
+
+    #[test]
+    fn documented_examples() {
+        let grid =
+            "grid:\n  columns: 4\n  rows: 3\n  first_id: 0\n  tag_size: 0.04\n  spacing: 0.01\n";
+        let board = Board::from_yaml(grid).unwrap();
+        assert_eq!(board.len(), 12);
+        let corner = board.tag_corners(11).unwrap()[0];
+        assert!(
+            (corner - Point3::new(0.13, 0.12, 0.0)).norm() < 1e-12,
+            "{corner}"
+        );
+
+        let tags = "tags:\n  - id: 3\n    corners: [[-0.02, 0.02, 0], [0.02, 0.02, 0], [0.02, -0.02, 0], [-0.02, -0.02, 0]]\n";
+        let board = Board::from_yaml(tags).unwrap();
+        assert_eq!(board.len(), 1);
+        assert_eq!(
+            board.tag_corners(3).unwrap()[1],
+            Point3::new(0.02, 0.02, 0.0)
+        );
+
+        let json = r#"{"grid": {"columns": 2, "rows": 1, "tag_size": 0.04, "spacing": 0.01}}"#;
+        assert_eq!(Board::from_yaml(json).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn grid_file_round_trip() {
+        let grid = GridSpec {
+            columns: 3,
+            rows: 2,
+            first_id: 1,
+            tag_size: 0.05,
+            spacing: 0.01,
+        };
+        let yaml = grid.to_yaml().unwrap();
+        assert!(yaml.starts_with("grid:\n"), "{yaml}");
+        let board = Board::from_yaml(&yaml).unwrap();
+        assert_eq!(board.tags, grid.board().tags);
+    }
+
+    #[test]
+    fn invalid_boards() {
+        for text in [
+            "grid:\n  columns: 1\n  rows: 1\n  tag_size: 0\n  spacing: 0\n",
+            "grid:\n  columns: 0\n  rows: 1\n  tag_size: 0.04\n  spacing: 0\n",
+            "tags: []\n",
+            "tags:\n  - id: 1\n    corners: [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0]]\n  - id: 1\n    corners: [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0]]\n",
+            "grid:\n  columns: 1\n  rows: 1\n  tag_size: 0.04\n  spacing: 0\n  margin: 1\n",
+        ] {
+            assert!(Board::from_yaml(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn grid_layout() {
+        let board = board();
+        assert_eq!(board.len(), 6);
+        assert!(board.tag_corners(0).is_none() && board.tag_corners(7).is_none());
+
+        // The first tag is centered at the origin, the fifth one row down and one column along
+        let first = board.tag_corners(1).unwrap();
+        for (corner, [x, y]) in first.iter().zip(TAG_CORNERS) {
+            assert_eq!(*corner, Point3::new(x * 0.025, y * 0.025, 0.0));
+        }
+        let (frame, half_size) = tag_frame(board.tag_corners(5).unwrap()).unwrap();
+        assert!((half_size - 0.025).abs() < 1e-12);
+        assert!((frame.translation.vector - Vector3::new(0.06, 0.06, 0.0)).norm() < 1e-12);
+        assert!(frame.rotation.angle() < 1e-12);
+    }
+
+    #[test]
+    fn pixel_area_of_square() {
+        let detection = tag_detection(0)
+            .with_corners([
+                [100.0, 200.0],
+                [200.0, 200.0],
+                [200.0, 100.0],
+                [100.0, 100.0],
+            ])
+            .unwrap();
+        assert!((pixel_area(&detection) - 10000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn view_of_board_tags() {
+        let model = truth();
+        let pose = &board_poses()[1];
+        let view = board()
+            .view(
+                &detect_board(&model, pose, &[1, 2, 3, 4, 5, 6]),
+                model.intrinsics(),
+            )
+            .unwrap();
+        assert_eq!(view.correspondences.len(), 24);
+        for (point, pixel) in &view.correspondences {
+            let projected = model.project(&(pose * point)).unwrap();
+            assert!((projected[0] - pixel[0]).abs() < 1e-9);
+            assert!((projected[1] - pixel[1]).abs() < 1e-9);
+        }
+        // The initial pose comes from the largest tag alone
+        assert!((view.initial_pose.translation.vector - pose.translation.vector).norm() < 1e-6);
+        assert!(view.initial_pose.rotation.angle_to(&pose.rotation) < 1e-6);
+
+        let refined = refine_pose(&view, &model).unwrap();
+        assert!((refined.translation.vector - pose.translation.vector).norm() < 1e-9);
+        assert!(refined.rotation.angle_to(&pose.rotation) < 1e-9);
+    }
+
+    #[test]
+    fn no_view_without_board_tags() {
+        let model = truth();
+        let detections = detect_board(&model, &board_poses()[0], &[]);
+        assert!(board().view(&detections, model.intrinsics()).is_none());
+    }
+
+    #[test]
+    fn calibrates_from_partial_views() {
+        let truth = truth();
+        let initial = CameraModel::Pinhole(Intrinsics::centered(IMAGE_WIDTH, IMAGE_HEIGHT, 750.0));
+        let visible: [&[usize]; 5] = [
+            &[1, 2, 3, 4, 5, 6],
+            &[1, 2, 4],
+            &[2, 3, 5, 6],
+            &[4, 5, 6],
+            &[1, 3, 5],
+        ];
+        let views: Vec<_> = board_poses()
+            .iter()
+            .zip(visible)
+            .map(|(pose, ids)| {
+                board()
+                    .view(&detect_board(&truth, pose, ids), initial.intrinsics())
+                    .unwrap()
+            })
+            .collect();
+
+        let calibration = calibrate_views(&views, &initial).unwrap();
+        assert!(calibration.rms_error < 1e-6, "{calibration:?}");
+        for (actual, expected) in calibration.model.params().iter().zip(truth.params()) {
+            assert!((actual - expected).abs() < 1e-4, "{calibration:?}");
+        }
+        for (pose, expected) in calibration.target_poses.iter().zip(board_poses()) {
+            assert!((pose.translation.vector - expected.translation.vector).norm() < 1e-6);
+        }
+    }
+}
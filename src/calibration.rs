@@ -0,0 +1,264 @@
+//! Joint calibration of a camera model from tag corner observations.
+
+use apriltag::detection::TAG_CORNERS;
+use apriltag_nalgebra::nalgebra::{
+    DVector, Isometry3, Matrix3, Point3, Rotation3, Translation3, UnitQuaternion,
+};
+
+use crate::{camera_model::CameraModel, least_squares::levenberg_marquardt, solver::Observation};
+
+/// A calibrated camera model.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub model: CameraModel,
+    /// The root mean square corner reprojection error in pixels.
+    pub rms_error: f64,
+    /// The pose of the target in camera coordinates for each view.
+    pub target_poses: Vec<Isometry3<f64>>,
+}
+
+/// Known target points detected in one image.
+#[derive(Debug, Clone)]
+pub struct View {
+    /// The points in target coordinates with the pixels they were detected at.
+    pub correspondences: Vec<(Point3<f64>, [f64; 2])>,
+    /// The initial pose of the target in camera coordinates.
+    pub initial_pose: Isometry3<f64>,
+}
+
+/// Estimates the parameters of a camera model by minimizing the reprojection
+/// error of every tag corner.
+///
+/// The tag poses are initialized from the homographies using the `initial`
+/// model's intrinsics and refined alongside the camera parameters with
+/// Levenberg-Marquardt, starting from the `initial` distortion. The skew is kept
+/// at its initial value. Each view adds eight residuals but six pose
+/// parameters, so the number of views needed depends on the model.
+/// Returns `None` if there are too few views or the refinement fails.
+pub fn calibrate_camera(
+    observations: &[Observation],
+    tag_width: f64,
+    initial: &CameraModel,
+) -> Option<Calibration> {
+    let half_size = tag_width / 2.0;
+    let inverse_camera = initial.intrinsics().matrix().try_inverse()?;
+    let views = observations
+        .iter()
+        .map(|observation| {
+            let homography = observation.detection.homography();
+            let homography = Matrix3::from_row_slice(homography.data());
+            Some(View {
+                correspondences: TAG_CORNERS
+                    .iter()
+                    .zip(observation.detection.corners())
+                    .map(|([x, y], pixel)| (Point3::new(x * half_size, y * half_size, 0.0), pixel))
+                    .collect(),
+                initial_pose: initial_pose(&homography, &inverse_camera, half_size)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    calibrate_views(&views, initial)
+}
+
+/// Estimates the parameters of a camera model and the target poses by
+/// minimizing the reprojection error of every target point in every view.
+///
+/// The camera parameters start from the `initial` model and the poses from the
+/// views' initial poses. The skew is kept at its initial value.
+/// Returns `None` if there are fewer residuals than parameters or the refinement fails.
+pub fn calibrate_views(views: &[View], initial: &CameraModel) -> Option<Calibration> {
+    let mut params = initial.params();
+    let camera_params = params.len();
+    let points: usize = views.iter().map(|view| view.correspondences.len()).sum();
+    if 2 * points < camera_params + 6 * views.len() {
+        return None;
+    }
+    for view in views {
+        params.extend(pose_params(&view.initial_pose));
+    }
+
+    let residuals = |params: &DVector<f64>| {
+        let model = initial.with_params(&params.as_slice()[..camera_params]);
+        let mut residuals = Vec::with_capacity(2 * points);
+        for (index, view) in views.iter().enumerate() {
+            let pose = pose_from_params(params, camera_params + 6 * index);
+            reprojection_residuals(view, &model, &pose, &mut residuals)?;
+        }
+        Some(DVector::from_vec(residuals))
+    };
+
+    let (params, residuals) = levenberg_marquardt(DVector::from_vec(params), residuals)?;
+    Some(Calibration {
+        model: initial.with_params(&params.as_slice()[..camera_params]),
+        rms_error: (residuals.norm_squared() / points as f64).sqrt(),
+        target_poses: (0..views.len())
+            .map(|index| pose_from_params(&params, camera_params + 6 * index))
+            .collect(),
+    })
+}
+
+/// Refines the pose of the target in one view for a fixed camera model by
+/// minimizing the reprojection error.
+///
+/// Returns `None` if the view has fewer than three points or the refinement fails.
+pub fn refine_pose(view: &View, model: &CameraModel) -> Option<Isometry3<f64>> {
+    if view.correspondences.len() < 3 {
+        return None;
+    }
+    let residuals = |params: &DVector<f64>| {
+        let mut residuals = Vec::with_capacity(2 * view.correspondences.len());
+        reprojection_residuals(view, model, &pose_from_params(params, 0), &mut residuals)?;
+        Some(DVector::from_vec(residuals))
+    };
+    let initial = DVector::from_row_slice(&pose_params(&view.initial_pose));
+    let (params, _) = levenberg_marquardt(initial, residuals)?;
+    Some(pose_from_params(&params, 0))
+}
+
+/// Appends the pixel offsets of the reprojected target points to `residuals`.
+///
+/// Returns `None` if a point is behind the camera.
+fn reprojection_residuals(
+    view: &View,
+    model: &CameraModel,
+    pose: &Isometry3<f64>,
+    residuals: &mut Vec<f64>,
+) -> Option<()> {
+    for (point, [u, v]) in &view.correspondences {
+        let [pu, pv] = model.project(&(pose * point))?;
+        residuals.extend([pu - u, pv - v]);
+    }
+    Some(())
+}
+
+/// Lists the rotation vector followed by the translation of a pose.
+fn pose_params(pose: &Isometry3<f64>) -> [f64; 6] {
+    let rotation = pose.rotation.scaled_axis();
+    let translation = pose.translation.vector;
+    [
+        rotation.x,
+        rotation.y,
+        rotation.z,
+        translation.x,
+        translation.y,
+        translation.z,
+    ]
+}
+
+/// Reads the pose stored as a rotation vector and translation at `offset`.
+fn pose_from_params(params: &DVector<f64>, offset: usize) -> Isometry3<f64> {
+    let pose = params.fixed_rows::<6>(offset);
+    Isometry3::new(
+        pose.fixed_rows::<3>(3).into_owned(),
+        pose.fixed_rows::<3>(0).into_owned(),
+    )
+}
+
+/// Decomposes a tag homography into the pose of the tag for the given inverse
+/// camera matrix.
+pub fn initial_pose(
+    homography: &Matrix3<f64>,
+    inverse_camera: &Matrix3<f64>,
+    half_size: f64,
+) -> Option<Isometry3<f64>> {
+    let mut extrinsics = inverse_camera * homography;
+    let norm = (extrinsics.column(0).norm() + extrinsics.column(1).norm()) / 2.0;
+    if norm == 0.0 {
+        return None;
+    }
+    extrinsics *= half_size / norm;
+    // Keep the tag in front of the camera
+    if extrinsics[(2, 2)] < 0.0 {
+        extrinsics = -extrinsics;
+    }
+
+    let r1 = extrinsics.column(0) / half_size;
+    let r2 = extrinsics.column(1) / half_size;
+    let rotation = Rotation3::from_matrix(&Matrix3::from_columns(&[r1, r2, r1.cross(&r2)]));
+    Some(Isometry3::from_parts(
+        Translation3::from(extrinsics.column(2).into_owned()),
+        UnitQuaternion::from_rotation_matrix(&rotation),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        distortion::Distortion,
+        intrinsics::Intrinsics,
+        synthetic::{observe_tag, tag_pose, template_detection, IMAGE_HEIGHT, IMAGE_WIDTH},
+    };
+    use apriltag::Detection;
+
+    const TAG_WIDTH: f64 = 0.1;
+
+    fn brown_conrady() -> CameraModel {
+        CameraModel::BrownConrady {
+            intrinsics: Intrinsics {
+                fx: 800.0,
+                fy: 810.0,
+                cx: 325.0,
+                cy: 235.0,
+                skew: 0.0,
+            },
+            distortion: Distortion {
+                k1: -0.2,
+                k2: 0.05,
+                k3: 0.0,
+                p1: 0.001,
+                p2: -0.0005,
+            },
+        }
+    }
+
+    /// Detects tags spread over the image, so that the distortion is observable.
+    fn detections(model: &CameraModel, count: usize) -> Vec<Detection> {
+        let template = template_detection();
+        (0..count)
+            .map(|index| {
+                let angle = index as f64 * 2.4;
+                let position = [0.25 * angle.cos(), 0.18 * angle.sin(), 0.8];
+                let tilt = [0.3 * angle.sin(), 0.3 * angle.cos(), 0.1 * index as f64];
+                observe_tag(&template, model, &tag_pose(tilt, position), TAG_WIDTH)
+            })
+            .collect()
+    }
+
+    fn observations(detections: &[Detection]) -> Vec<Observation<'_>> {
+        detections
+            .iter()
+            .map(|detection| Observation {
+                detection,
+                distance: 0.8,
+                image_width: IMAGE_WIDTH,
+                image_height: IMAGE_HEIGHT,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovers_brown_conrady_model() {
+        let truth = brown_conrady();
+        let detections = detections(&truth, 12);
+        let initial = CameraModel::undistorted(
+            truth.kind(),
+            Intrinsics::centered(IMAGE_WIDTH, IMAGE_HEIGHT, 750.0),
+        );
+
+        let calibration =
+            calibrate_camera(&observations(&detections), TAG_WIDTH, &initial).unwrap();
+        assert!(calibration.rms_error < 1e-6, "{calibration:?}");
+        for (actual, expected) in calibration.model.params().iter().zip(truth.params()) {
+            assert!((actual - expected).abs() < 1e-4, "{calibration:?}");
+        }
+    }
+
+    #[test]
+    fn too_few_residuals() {
+        // Two views give 16 residuals for 9 camera and 12 pose parameters
+        let truth = brown_conrady();
+        let detections = detections(&truth, 2);
+        assert!(calibrate_camera(&observations(&detections), TAG_WIDTH, &truth).is_none());
+    }
+}
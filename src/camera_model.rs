@@ -0,0 +1,307 @@
+//! Camera models shared by pose and focal length estimation.
+
+use apriltag::{
+    calibration::{self, CameraCalibration},
+    Detection, Pose,
+};
+use apriltag_nalgebra::nalgebra::Point3;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    distortion::{Distortion, FisheyeDistortion},
+    intrinsics::Intrinsics,
+};
+
+/// The kinds of [CameraModel], as selected on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CameraModelKind {
+    /// Ideal pinhole camera without distortion
+    Pinhole,
+    /// Pinhole camera with Brown-Conrady radial and tangential distortion
+    BrownConrady,
+    /// Kannala-Brandt equidistant fisheye camera
+    KannalaBrandt,
+}
+
+/// A camera model mapping points in camera coordinates to pixels.
+#[derive(Debug, Clone)]
+pub enum CameraModel {
+    Pinhole(Intrinsics),
+    BrownConrady {
+        intrinsics: Intrinsics,
+        distortion: Distortion,
+    },
+    KannalaBrandt {
+        intrinsics: Intrinsics,
+        distortion: FisheyeDistortion,
+    },
+}
+
+impl CameraModel {
+    /// Creates a distortion-free model of the given kind.
+    pub fn undistorted(kind: CameraModelKind, intrinsics: Intrinsics) -> Self {
+        match kind {
+            CameraModelKind::Pinhole => Self::Pinhole(intrinsics),
+            CameraModelKind::BrownConrady => Self::BrownConrady {
+                intrinsics,
+                distortion: Distortion::default(),
+            },
+            CameraModelKind::KannalaBrandt => Self::KannalaBrandt {
+                intrinsics,
+                distortion: FisheyeDistortion::default(),
+            },
+        }
+    }
+
+    /// Gets the kind of the model.
+    pub fn kind(&self) -> CameraModelKind {
+        match self {
+            Self::Pinhole(_) => CameraModelKind::Pinhole,
+            Self::BrownConrady { .. } => CameraModelKind::BrownConrady,
+            Self::KannalaBrandt { .. } => CameraModelKind::KannalaBrandt,
+        }
+    }
+
+    /// Gets the intrinsics of the model.
+    pub fn intrinsics(&self) -> &Intrinsics {
+        match self {
+            Self::Pinhole(intrinsics)
+            | Self::BrownConrady { intrinsics, .. }
+            | Self::KannalaBrandt { intrinsics, .. } => intrinsics,
+        }
+    }
+
+    fn intrinsics_mut(&mut self) -> &mut Intrinsics {
+        match self {
+            Self::Pinhole(intrinsics)
+            | Self::BrownConrady { intrinsics, .. }
+            | Self::KannalaBrandt { intrinsics, .. } => intrinsics,
+        }
+    }
+
+    /// Creates a copy of the model with `fx` replaced, scaling `fy` to keep the aspect ratio.
+    pub fn with_focal_length(&self, fx: f64) -> Self {
+        let mut model = self.clone();
+        let intrinsics = model.intrinsics_mut();
+        intrinsics.fy *= fx / intrinsics.fx;
+        intrinsics.fx = fx;
+        model
+    }
+
+    /// Returns the parameters refined during calibration: `fx`, `fy`, `cx`, `cy`
+    /// followed by the distortion coefficients.
+    pub fn params(&self) -> Vec<f64> {
+        let Intrinsics { fx, fy, cx, cy, .. } = *self.intrinsics();
+        let mut params = vec![fx, fy, cx, cy];
+        match self {
+            Self::Pinhole(_) => {}
+            Self::BrownConrady { distortion, .. } => {
+                let Distortion { k1, k2, k3, p1, p2 } = *distortion;
+                params.extend([k1, k2, k3, p1, p2]);
+            }
+            Self::KannalaBrandt { distortion, .. } => {
+                let FisheyeDistortion { k1, k2, k3, k4 } = *distortion;
+                params.extend([k1, k2, k3, k4]);
+            }
+        }
+        params
+    }
+
+    /// Creates a copy of the model with the parameters in the layout of [params](Self::params).
+    pub fn with_params(&self, params: &[f64]) -> Self {
+        let intrinsics = Intrinsics {
+            fx: params[0],
+            fy: params[1],
+            cx: params[2],
+            cy: params[3],
+            skew: self.intrinsics().skew,
+        };
+        match self {
+            Self::Pinhole(_) => Self::Pinhole(intrinsics),
+            Self::BrownConrady { .. } => Self::BrownConrady {
+                intrinsics,
+                distortion: Distortion {
+                    k1: params[4],
+                    k2: params[5],
+                    k3: params[6],
+                    p1: params[7],
+                    p2: params[8],
+                },
+            },
+            Self::KannalaBrandt { .. } => Self::KannalaBrandt {
+                intrinsics,
+                distortion: FisheyeDistortion {
+                    k1: params[4],
+                    k2: params[5],
+                    k3: params[6],
+                    k4: params[7],
+                },
+            },
+        }
+    }
+
+    /// Projects a point in camera coordinates to a pixel.
+    ///
+    /// Returns `None` for points behind the camera.
+    pub fn project(&self, point: &Point3<f64>) -> Option<[f64; 2]> {
+        if point.z <= 0.0 {
+            return None;
+        }
+        let normalized = [point.x / point.z, point.y / point.z];
+        let distorted = match self {
+            Self::Pinhole(_) => normalized,
+            Self::BrownConrady { distortion, .. } => distortion.distort(normalized),
+            Self::KannalaBrandt { distortion, .. } => distortion.distort(normalized),
+        };
+        Some(self.intrinsics().denormalize(distorted))
+    }
+
+    /// Maps a pixel to where an ideal pinhole camera with the same intrinsics
+    /// would have imaged it.
+    pub fn undistort_point(&self, pixel: [f64; 2]) -> [f64; 2] {
+        let intrinsics = self.intrinsics();
+        let normalized = intrinsics.normalize(pixel);
+        let undistorted = match self {
+            Self::Pinhole(_) => return pixel,
+            Self::BrownConrady { distortion, .. } => distortion.undistort(normalized),
+            Self::KannalaBrandt { distortion, .. } => distortion.undistort(normalized),
+        };
+        intrinsics.denormalize(undistorted)
+    }
+
+    /// Estimates the pose of a tag of the given width.
+    ///
+    /// The corners are undistorted first so that AprilTag's pinhole pose solver
+    /// can be used with the model's intrinsics.
+    pub fn estimate_tag_pose(&self, detection: &Detection, tag_width: f64) -> Option<Pose> {
+        let params = self.intrinsics().tag_params(tag_width);
+        if let Self::Pinhole(_) = self {
+            return detection.estimate_tag_pose(&params);
+        }
+        let undistorted = detection.with_corners(
+            detection
+                .corners()
+                .map(|corner| self.undistort_point(corner)),
+        )?;
+        undistorted.estimate_tag_pose(&params)
+    }
+}
+
+impl From<&CameraCalibration> for CameraModel {
+    fn from(calibration: &CameraCalibration) -> Self {
+        let intrinsics = Intrinsics {
+            fx: calibration.fx,
+            fy: calibration.fy,
+            cx: calibration.cx,
+            cy: calibration.cy,
+            skew: calibration.skew,
+        };
+        match calibration.distortion {
+            calibration::Distortion::None => Self::Pinhole(intrinsics),
+            calibration::Distortion::BrownConrady { k1, k2, k3, p1, p2 } => Self::BrownConrady {
+                intrinsics,
+                distortion: Distortion { k1, k2, k3, p1, p2 },
+            },
+            calibration::Distortion::KannalaBrandt { k1, k2, k3, k4 } => Self::KannalaBrandt {
+                intrinsics,
+                distortion: FisheyeDistortion { k1, k2, k3, k4 },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{observe_tag, tag_pose, template_detection};
+    use apriltag_nalgebra::nalgebra::Matrix3;
+
+    fn intrinsics(skew: f64) -> Intrinsics {
+        Intrinsics {
+            fx: 800.0,
+            fy: 810.0,
+            cx: 325.0,
+            cy: 235.0,
+            skew,
+        }
+    }
+
+    fn models(skew: f64) -> [CameraModel; 3] {
+        [
+            CameraModel::Pinhole(intrinsics(skew)),
+            CameraModel::BrownConrady {
+                intrinsics: intrinsics(skew),
+                distortion: Distortion {
+                    k1: -0.2,
+                    k2: 0.05,
+                    k3: 0.01,
+                    p1: 0.001,
+                    p2: -0.0005,
+                },
+            },
+            CameraModel::KannalaBrandt {
+                intrinsics: intrinsics(skew),
+                distortion: FisheyeDistortion {
+                    k1: 0.1,
+                    k2: -0.02,
+                    k3: 0.003,
+                    k4: -0.0004,
+                },
+            },
+        ]
+    }
+
+    #[test]
+    fn params_round_trip() {
+        for (model, count) in models(0.5).into_iter().zip([4, 9, 8]) {
+            let params = model.params();
+            assert_eq!(params.len(), count);
+            let copy = model.with_params(&params);
+            assert_eq!(copy.kind(), model.kind());
+            assert_eq!(copy.params(), params);
+            assert_eq!(copy.intrinsics().skew, model.intrinsics().skew);
+
+            // Every parameter lands in its own place
+            let shifted: Vec<_> = params.iter().map(|param| param + 1.0).collect();
+            assert_eq!(model.with_params(&shifted).params(), shifted);
+        }
+    }
+
+    #[test]
+    fn undistort_inverts_project() {
+        for model in models(0.5) {
+            for point in [[0.0, 0.0, 1.0], [0.2, -0.1, 0.8], [-0.3, 0.25, 1.5]] {
+                let [x, y, z] = point;
+                let pixel = model.project(&Point3::new(x, y, z)).unwrap();
+                let [u, v] = model.intrinsics().normalize(model.undistort_point(pixel));
+                assert!((u - x / z).abs() < 1e-9, "{model:?} {point:?}");
+                assert!((v - y / z).abs() < 1e-9, "{model:?} {point:?}");
+            }
+            assert!(model.project(&Point3::new(0.1, 0.1, -1.0)).is_none());
+        }
+    }
+
+    #[test]
+    fn estimates_pose_of_distorted_tag() {
+        let template = template_detection();
+        let pose = tag_pose([0.3, -0.2, 0.1], [0.08, -0.05, 0.6]);
+        let rotation = pose.rotation.to_rotation_matrix();
+        // The pose solver of the library has no skew
+        for model in models(0.0) {
+            let detection = observe_tag(&template, &model, &pose, 0.1);
+            let estimate = model.estimate_tag_pose(&detection, 0.1).unwrap();
+            let translation = estimate.translation();
+            for (actual, expected) in translation
+                .data()
+                .iter()
+                .zip(pose.translation.vector.iter())
+            {
+                assert!((actual - expected).abs() < 1e-6, "{model:?}");
+            }
+            let estimated = Matrix3::from_row_slice(estimate.rotation().data());
+            assert!((estimated - rotation.matrix()).norm() < 1e-6, "{model:?}");
+        }
+    }
+}
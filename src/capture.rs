@@ -0,0 +1,52 @@
+//! Camera capture sessions.
+
+use image::GrayImage;
+use nokhwa::{
+    pixel_format::LumaFormat,
+    utils::{CameraIndex, RequestedFormat, RequestedFormatType},
+    Camera,
+};
+
+/// An open camera stream handing back grayscale frames on demand.
+///
+/// Keeping one stream open across captures lets auto-exposure and autofocus
+/// settle instead of starting over for every frame.
+pub struct CaptureSession {
+    camera: Camera,
+}
+
+impl CaptureSession {
+    /// Opens the stream of the camera with the given index and discards the
+    /// first `warmup_frames` frames.
+    pub fn open(camera_index: u32, warmup_frames: usize) -> anyhow::Result<Self> {
+        let requested =
+            RequestedFormat::new::<LumaFormat>(RequestedFormatType::AbsoluteHighestResolution);
+        let mut camera = Camera::new(CameraIndex::Index(camera_index), requested)?;
+        camera.open_stream()?;
+
+        let mut session = Self { camera };
+        session.discard(warmup_frames)?;
+        Ok(session)
+    }
+
+    /// Grabs and decodes the next frame.
+    pub fn frame(&mut self) -> anyhow::Result<GrayImage> {
+        Ok(self.camera.frame()?.decode_image::<LumaFormat>()?)
+    }
+
+    /// Grabs and drops the given number of frames, e.g. the stale frames
+    /// buffered while waiting for the operator.
+    pub fn discard(&mut self, frames: usize) -> anyhow::Result<()> {
+        for _ in 0..frames {
+            self.camera.frame()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CaptureSession {
+    fn drop(&mut self) {
+        // The stream is closed when the camera is dropped anyway, so errors are moot
+        let _ = self.camera.stop_stream();
+    }
+}
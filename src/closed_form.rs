@@ -0,0 +1,169 @@
+//! Closed-form focal length estimator based on the pinhole model.
+
+use apriltag_nalgebra::nalgebra::{DMatrix, DVector, Matrix3};
+
+use crate::solver::Observation;
+
+/// The focal lengths along both image axes recovered by the closed-form estimator.
+#[derive(Debug, Clone)]
+pub struct ClosedFormEstimate {
+    pub fx: f64,
+    pub fy: f64,
+}
+
+/// Computes `fx` and `fy` in pixels directly from the tag homographies and the
+/// measured distances, assuming the principal point lies at the image center.
+///
+/// AprilTag fits the homography to the detected corners, mapping the ideal tag
+/// square with corners at `(±1, ±1)` to pixels. Up to scale it equals
+/// `K [s·r1, s·r2, t]` with `s` half the tag width, so the unit length and
+/// orthogonality of `r1`/`r2` account for the tilt that foreshortens the corner
+/// edge lengths, while `|t|` being the measured distance fixes the scale. The
+/// resulting equations are linear in `1/fx²`, `1/fy²` and one scale per
+/// observation, and are solved in the least-squares sense.
+///
+/// Returns `None` if the system is degenerate or yields a non-positive focal length.
+pub fn closed_form_focal_length(
+    observations: &[Observation],
+    tag_width: f64,
+) -> Option<ClosedFormEstimate> {
+    let width = observations
+        .iter()
+        .map(|observation| observation.image_width)
+        .max()? as f64;
+    let half_size = tag_width / 2.0;
+
+    // Unknowns are [width²/fx², width²/fy², scale_1, ..., scale_n]
+    let ncols = 2 + observations.len();
+    let nrows = 4 * observations.len();
+    let mut matrix = DMatrix::zeros(nrows, ncols);
+    let mut rhs = DVector::zeros(nrows);
+
+    for (index, observation) in observations.iter().enumerate() {
+        let homography = observation.detection.homography();
+        let homography = Matrix3::from_row_slice(homography.data());
+        let cx = observation.image_width as f64 / 2.0;
+        let cy = observation.image_height as f64 / 2.0;
+        let centering = Matrix3::new(
+            1.0 / width,
+            0.0,
+            -cx / width,
+            0.0,
+            1.0 / width,
+            -cy / width,
+            0.0,
+            0.0,
+            1.0,
+        );
+        let g = centering * homography;
+        let g = g / g.norm();
+
+        let scale_col = 2 + index;
+        let rows = [
+            // |r1| = 1
+            (
+                [g[(0, 0)].powi(2), g[(1, 0)].powi(2)],
+                -half_size.powi(2),
+                -g[(2, 0)].powi(2),
+            ),
+            // |r2| = 1
+            (
+                [g[(0, 1)].powi(2), g[(1, 1)].powi(2)],
+                -half_size.powi(2),
+                -g[(2, 1)].powi(2),
+            ),
+            // r1 · r2 = 0
+            (
+                [g[(0, 0)] * g[(0, 1)], g[(1, 0)] * g[(1, 1)]],
+                0.0,
+                -g[(2, 0)] * g[(2, 1)],
+            ),
+            // |t| = distance
+            (
+                [g[(0, 2)].powi(2), g[(1, 2)].powi(2)],
+                -observation.distance.powi(2),
+                -g[(2, 2)].powi(2),
+            ),
+        ];
+
+        for (offset, ([a, b], scale, value)) in rows.into_iter().enumerate() {
+            let row = 4 * index + offset;
+            matrix[(row, 0)] = a;
+            matrix[(row, 1)] = b;
+            matrix[(row, scale_col)] = scale;
+            rhs[row] = value;
+        }
+    }
+
+    let solution = matrix.svd(true, true).solve(&rhs, 1e-12).ok()?;
+    let (a, b) = (solution[0], solution[1]);
+    if !(a > 0.0 && b > 0.0) {
+        return None;
+    }
+
+    Some(ClosedFormEstimate {
+        fx: width / a.sqrt(),
+        fy: width / b.sqrt(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera_model::CameraModel,
+        intrinsics::Intrinsics,
+        synthetic::{observe_tag, tag_pose, template_detection, IMAGE_HEIGHT, IMAGE_WIDTH},
+    };
+    use apriltag::Detection;
+    use apriltag_nalgebra::nalgebra::Isometry3;
+
+    const TAG_WIDTH: f64 = 0.1;
+
+    fn estimate(poses: &[Isometry3<f64>]) -> Option<ClosedFormEstimate> {
+        let model = CameraModel::Pinhole(Intrinsics {
+            fy: 820.0,
+            ..Intrinsics::centered(IMAGE_WIDTH, IMAGE_HEIGHT, 800.0)
+        });
+        let template = template_detection();
+        let detections: Vec<Detection> = poses
+            .iter()
+            .map(|pose| observe_tag(&template, &model, pose, TAG_WIDTH))
+            .collect();
+        let observations: Vec<_> = detections
+            .iter()
+            .zip(poses)
+            .map(|(detection, pose)| Observation {
+                detection,
+                distance: pose.translation.vector.norm(),
+                image_width: IMAGE_WIDTH,
+                image_height: IMAGE_HEIGHT,
+            })
+            .collect();
+        closed_form_focal_length(&observations, TAG_WIDTH)
+    }
+
+    #[test]
+    fn recovers_focal_lengths_of_tilted_views() {
+        let estimate = estimate(&[
+            tag_pose([0.4, 0.0, 0.1], [0.05, 0.02, 0.6]),
+            tag_pose([0.0, -0.5, 0.0], [-0.04, 0.03, 0.9]),
+            tag_pose([0.3, 0.3, -0.2], [0.0, -0.05, 1.1]),
+        ])
+        .unwrap();
+        assert!((estimate.fx - 800.0).abs() < 1e-3, "{estimate:?}");
+        assert!((estimate.fy - 820.0).abs() < 1e-3, "{estimate:?}");
+    }
+
+    #[test]
+    fn recovers_focal_lengths_of_fronto_parallel_view() {
+        let estimate = estimate(&[tag_pose([0.0, 0.0, 0.0], [0.0, 0.0, 0.5])]).unwrap();
+        assert!((estimate.fx - 800.0).abs() < 1e-3, "{estimate:?}");
+        assert!((estimate.fy - 820.0).abs() < 1e-3, "{estimate:?}");
+    }
+
+    #[test]
+    fn no_estimate_without_observations() {
+        assert!(closed_form_focal_length(&[], TAG_WIDTH).is_none());
+    }
+}
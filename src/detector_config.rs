@@ -0,0 +1,240 @@
+//! Detector tuning options from the command line and config files.
+
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use apriltag::Detector;
+use clap::Args;
+use measurements::angle::Angle;
+use noisy_float::prelude::R32;
+use serde::Deserialize;
+
+/// Tuning options of the AprilTag detector, from the command line or the
+/// `detector` section of a config file. Unset options keep the library defaults.
+#[derive(Debug, Clone, Default, Args, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectorConfig {
+    /// The number of threads used for detection
+    #[clap(long, global = true)]
+    pub threads: Option<u8>,
+
+    /// Detect quads on an image decimated by this factor, faster but less accurate (default 2)
+    #[clap(long, global = true)]
+    pub decimation: Option<f32>,
+
+    /// The standard deviation in pixels of the Gaussian blur applied before quad detection,
+    /// e.g. 0.8 for noisy images (default 0)
+    #[clap(long, global = true)]
+    pub sigma: Option<f32>,
+
+    /// Snap the quad edges to strong nearby gradients (default true)
+    #[clap(long, global = true)]
+    pub refine_edges: Option<bool>,
+
+    /// The amount of sharpening applied to the decoded images (default 0.25)
+    #[clap(long, global = true)]
+    pub sharpening: Option<f64>,
+
+    /// The minimal number of pixels of a quad candidate
+    #[clap(long, global = true)]
+    pub min_cluster_pixels: Option<u32>,
+
+    /// The number of corner candidates considered when segmenting a quad
+    #[clap(long, global = true)]
+    pub max_maxima_number: Option<u32>,
+
+    /// Reject quads with adjacent edges within this angle of parallel, in degrees (default 10)
+    #[clap(long, global = true)]
+    pub min_angle: Option<f64>,
+
+    /// The maximal mean squared error when fitting lines to the quad contour
+    #[clap(long, global = true)]
+    pub max_mse: Option<f32>,
+
+    /// The minimal difference in gray level between the white and black parts of a tag,
+    /// raise it for low contrast scenes
+    #[clap(long, global = true)]
+    pub min_white_black_diff: Option<u8>,
+
+    /// Deglitch the thresholded image, useful for very noisy images
+    #[clap(long, global = true)]
+    pub deglitch: Option<bool>,
+}
+
+impl DetectorConfig {
+    /// Loads the `detector` section of a YAML config file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct ConfigFile {
+            #[serde(default)]
+            detector: DetectorConfig,
+        }
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config '{}'", path.display()))?;
+        let config: ConfigFile = serde_yaml::from_str(&text)
+            .with_context(|| format!("invalid config '{}'", path.display()))?;
+        Ok(config.detector)
+    }
+
+    /// Fills the options unset in `self` from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            threads: self.threads.or(fallback.threads),
+            decimation: self.decimation.or(fallback.decimation),
+            sigma: self.sigma.or(fallback.sigma),
+            refine_edges: self.refine_edges.or(fallback.refine_edges),
+            sharpening: self.sharpening.or(fallback.sharpening),
+            min_cluster_pixels: self.min_cluster_pixels.or(fallback.min_cluster_pixels),
+            max_maxima_number: self.max_maxima_number.or(fallback.max_maxima_number),
+            min_angle: self.min_angle.or(fallback.min_angle),
+            max_mse: self.max_mse.or(fallback.max_mse),
+            min_white_black_diff: self.min_white_black_diff.or(fallback.min_white_black_diff),
+            deglitch: self.deglitch.or(fallback.deglitch),
+        }
+    }
+
+    /// Applies the set options to the detector.
+    pub fn apply(&self, detector: &mut Detector) -> anyhow::Result<()> {
+        if let Some(threads) = self.threads {
+            detector.set_thread_number(threads);
+        }
+        if let Some(decimation) = self.decimation {
+            ensure!(decimation >= 1.0, "the decimation must be at least 1");
+            detector.set_decimation(decimation);
+        }
+        if let Some(sigma) = self.sigma {
+            detector.set_sigma(sigma);
+        }
+        if let Some(refine_edges) = self.refine_edges {
+            detector.set_refine_edges(refine_edges);
+        }
+        if let Some(sharpening) = self.sharpening {
+            detector.set_shapening(sharpening);
+        }
+
+        let mut thresholds = detector.thresholds();
+        if let Some(min_cluster_pixels) = self.min_cluster_pixels {
+            thresholds.min_cluster_pixels = min_cluster_pixels;
+        }
+        if let Some(max_maxima_number) = self.max_maxima_number {
+            thresholds.max_maxima_number = max_maxima_number;
+        }
+        if let Some(min_angle) = self.min_angle {
+            ensure!(
+                min_angle > 0.0 && min_angle < 90.0,
+                "the minimal angle must be between 0 and 90 degrees"
+            );
+            thresholds.min_angle = Angle::from_degrees(min_angle);
+        }
+        if let Some(max_mse) = self.max_mse {
+            thresholds.max_mse =
+                R32::try_new(max_mse).context("the maximal mean squared error must be a number")?;
+        }
+        if let Some(min_white_black_diff) = self.min_white_black_diff {
+            thresholds.min_white_black_diff = min_white_black_diff;
+        }
+        if let Some(deglitch) = self.deglitch {
+            thresholds.deglitch = deglitch;
+        }
+        detector.set_thresholds(thresholds);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apriltag::{DetectorBuilder, Family};
+
+    fn detector() -> Detector {
+        DetectorBuilder::new()
+            .add_family_bits(Family::tag_36h11(), 1)
+            .build()
+            .unwrap()
+    }
+
+    /// Writes the text to a temporary config file and loads it.
+    fn load(name: &str, text: &str) -> anyhow::Result<DetectorConfig> {
+        let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let config = DetectorConfig::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn command_line_overrides_file() {
+        let command_line = DetectorConfig {
+            threads: Some(2),
+            min_angle: Some(15.0),
+            ..Default::default()
+        };
+        let file = DetectorConfig {
+            threads: Some(4),
+            decimation: Some(1.5),
+            ..Default::default()
+        };
+        let config = command_line.or(file);
+        assert_eq!(config.threads, Some(2));
+        assert_eq!(config.min_angle, Some(15.0));
+        assert_eq!(config.decimation, Some(1.5));
+        assert_eq!(config.sigma, None);
+    }
+
+    #[test]
+    fn loads_yaml_and_json() {
+        let yaml = "detector:\n  decimation: 1.0\n  min_angle: 12.5\n  deglitch: true\n";
+        let json = r#"{"detector": {"decimation": 1.0, "min_angle": 12.5, "deglitch": true}}"#;
+        for (name, text) in [("config.yaml", yaml), ("config.json", json)] {
+            let config = load(name, text).unwrap();
+            assert_eq!(config.decimation, Some(1.0));
+            assert_eq!(config.min_angle, Some(12.5));
+            assert_eq!(config.deglitch, Some(true));
+            assert_eq!(config.threads, None);
+        }
+
+        // Other sections are ignored, but not unknown detector options
+        assert_eq!(load("other.yaml", "board: {}\n").unwrap().threads, None);
+        assert!(load("unknown.yaml", "detector:\n  min_opposite_angle: 1\n").is_err());
+    }
+
+    #[test]
+    fn applies_min_angle_in_degrees() {
+        let mut detector = detector();
+        let config = DetectorConfig {
+            min_angle: Some(20.0),
+            max_mse: Some(5.0),
+            ..Default::default()
+        };
+        config.apply(&mut detector).unwrap();
+        let thresholds = detector.thresholds();
+        assert!((thresholds.min_angle.as_degrees() - 20.0).abs() < 1e-4);
+        assert_eq!(thresholds.max_mse.raw(), 5.0);
+
+        let ptr = detector.into_raw();
+        let cos_critical_rad = unsafe { ptr.as_ref().qtp.cos_critical_rad };
+        drop(unsafe { Detector::from_raw(ptr.as_ptr()) });
+        assert_eq!(cos_critical_rad, 20f64.to_radians().cos() as f32);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        for config in [
+            DetectorConfig {
+                min_angle: Some(90.0),
+                ..Default::default()
+            },
+            DetectorConfig {
+                decimation: Some(0.5),
+                ..Default::default()
+            },
+            DetectorConfig {
+                max_mse: Some(f32::NAN),
+                ..Default::default()
+            },
+        ] {
+            assert!(config.apply(&mut detector()).is_err(), "{config:?}");
+        }
+    }
+}
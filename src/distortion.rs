@@ -0,0 +1,154 @@
+//! Lens distortion models in normalized image coordinates.
+
+/// The number of iterations used to invert the distortion.
+const UNDISTORT_ITERATIONS: usize = 20;
+
+/// Brown-Conrady radial (`k1`, `k2`, `k3`) and tangential (`p1`, `p2`)
+/// distortion coefficients, in the same convention as OpenCV.
+#[derive(Debug, Clone, Default)]
+pub struct Distortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    pub p1: f64,
+    pub p2: f64,
+}
+
+impl Distortion {
+    /// Applies the distortion to normalized image coordinates.
+    pub fn distort(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let Self { k1, k2, k3, p1, p2 } = *self;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+        [
+            x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+            y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+        ]
+    }
+
+    /// Removes the distortion from normalized image coordinates by fixed-point iteration.
+    pub fn undistort(&self, [xd, yd]: [f64; 2]) -> [f64; 2] {
+        let Self { k1, k2, k3, p1, p2 } = *self;
+        let (mut x, mut y) = (xd, yd);
+        for _ in 0..UNDISTORT_ITERATIONS {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+            let dx = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+            let dy = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+            x = (xd - dx) / radial;
+            y = (yd - dy) / radial;
+        }
+        [x, y]
+    }
+}
+
+/// Kannala-Brandt equidistant fisheye coefficients, in the same convention as
+/// OpenCV's fisheye module.
+///
+/// The distorted radius is `θ (1 + k1 θ² + k2 θ⁴ + k3 θ⁶ + k4 θ⁸)`, where `θ` is
+/// the angle between the incoming ray and the optical axis.
+#[derive(Debug, Clone, Default)]
+pub struct FisheyeDistortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    pub k4: f64,
+}
+
+impl FisheyeDistortion {
+    /// Applies the distortion to normalized image coordinates.
+    pub fn distort(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let radius = (x * x + y * y).sqrt();
+        if radius == 0.0 {
+            return [x, y];
+        }
+        let scale = self.distorted_angle(radius.atan()) / radius;
+        [x * scale, y * scale]
+    }
+
+    /// Removes the distortion from normalized image coordinates by Newton's method.
+    ///
+    /// Points at or beyond 90° from the optical axis have no pinhole equivalent
+    /// and come out at infinity.
+    pub fn undistort(&self, [xd, yd]: [f64; 2]) -> [f64; 2] {
+        let distorted_radius = (xd * xd + yd * yd).sqrt();
+        if distorted_radius == 0.0 {
+            return [xd, yd];
+        }
+        let Self { k1, k2, k3, k4 } = *self;
+        let mut theta = distorted_radius;
+        for _ in 0..UNDISTORT_ITERATIONS {
+            let theta2 = theta * theta;
+            let derivative = 1.0
+                + theta2
+                    * (3.0 * k1 + theta2 * (5.0 * k2 + theta2 * (7.0 * k3 + theta2 * 9.0 * k4)));
+            theta -= (self.distorted_angle(theta) - distorted_radius) / derivative;
+        }
+        let scale = theta.tan() / distorted_radius;
+        [xd * scale, yd * scale]
+    }
+
+    fn distorted_angle(&self, theta: f64) -> f64 {
+        let Self { k1, k2, k3, k4 } = *self;
+        let theta2 = theta * theta;
+        theta * (1.0 + theta2 * (k1 + theta2 * (k2 + theta2 * (k3 + theta2 * k4))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [[f64; 2]; 4] = [[0.0, 0.0], [0.3, -0.2], [-0.5, 0.4], [0.6, 0.6]];
+
+    fn assert_close([x, y]: [f64; 2], [expected_x, expected_y]: [f64; 2]) {
+        assert!(
+            (x - expected_x).abs() < 1e-9 && (y - expected_y).abs() < 1e-9,
+            "[{x}, {y}] != [{expected_x}, {expected_y}]"
+        );
+    }
+
+    #[test]
+    fn brown_conrady_round_trip() {
+        let distortion = Distortion {
+            k1: -0.2,
+            k2: 0.05,
+            k3: -0.01,
+            p1: 0.001,
+            p2: -0.0005,
+        };
+        for point in POINTS {
+            assert_close(distortion.undistort(distortion.distort(point)), point);
+        }
+    }
+
+    #[test]
+    fn brown_conrady_radial_scaling() {
+        let distortion = Distortion {
+            k1: 0.1,
+            ..Distortion::default()
+        };
+        // r² = 0.25, so the radius grows by 1 + 0.1 · 0.25
+        assert_close(distortion.distort([0.5, 0.0]), [0.5125, 0.0]);
+    }
+
+    #[test]
+    fn fisheye_round_trip() {
+        let distortion = FisheyeDistortion {
+            k1: 0.05,
+            k2: -0.01,
+            k3: 0.002,
+            k4: -0.0005,
+        };
+        for point in POINTS {
+            assert_close(distortion.undistort(distortion.distort(point)), point);
+        }
+    }
+
+    #[test]
+    fn undistorted_fisheye_is_equidistant() {
+        // Without coefficients, the distorted radius is the angle to the optical axis
+        let [x, y] = FisheyeDistortion::default().distort([1.0, 0.0]);
+        assert_close([x, y], [std::f64::consts::FRAC_PI_4, 0.0]);
+    }
+}
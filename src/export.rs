@@ -0,0 +1,354 @@
+//! Calibration result files for OpenCV, ROS and plain JSON consumers.
+
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera_model::{CameraModel, CameraModelKind},
+    distortion::{Distortion, FisheyeDistortion},
+    intrinsics::Intrinsics,
+};
+
+/// A calibration result together with the capture setup it was estimated from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationRecord {
+    pub image_width: usize,
+    pub image_height: usize,
+    /// The index of the camera as it appears to the OS, if the frames were captured live.
+    pub camera_index: Option<u32>,
+    /// The time of the calibration in RFC 3339 format.
+    pub timestamp: String,
+    pub model: CameraModelKind,
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub skew: f64,
+    /// The distortion coefficients in OpenCV order: `k1, k2, p1, p2, k3` for
+    /// Brown-Conrady, `k1, k2, k3, k4` for Kannala-Brandt and empty for pinhole.
+    pub distortion: Vec<f64>,
+    /// The RMS corner reprojection error in pixels, if the model was refined.
+    pub rms_error: Option<f64>,
+}
+
+impl CalibrationRecord {
+    /// Creates a record of the camera model timestamped with the current time.
+    pub fn new(
+        model: &CameraModel,
+        rms_error: Option<f64>,
+        image_width: usize,
+        image_height: usize,
+        camera_index: Option<u32>,
+    ) -> Self {
+        let Intrinsics {
+            fx,
+            fy,
+            cx,
+            cy,
+            skew,
+        } = *model.intrinsics();
+        let distortion = match model {
+            CameraModel::Pinhole(_) => vec![],
+            CameraModel::BrownConrady { distortion, .. } => {
+                let Distortion { k1, k2, k3, p1, p2 } = *distortion;
+                vec![k1, k2, p1, p2, k3]
+            }
+            CameraModel::KannalaBrandt { distortion, .. } => {
+                let FisheyeDistortion { k1, k2, k3, k4 } = *distortion;
+                vec![k1, k2, k3, k4]
+            }
+        };
+
+        Self {
+            image_width,
+            image_height,
+            camera_index,
+            timestamp: rfc3339_now(),
+            model: model.kind(),
+            fx,
+            fy,
+            cx,
+            cy,
+            skew,
+            distortion,
+            rms_error,
+        }
+    }
+
+    /// Serializes the record as a JSON document.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Serializes the record in the YAML dialect of OpenCV's `cv::FileStorage`.
+    ///
+    /// The `distortion_model` key tells the `cv::fisheye` coefficients apart from
+    /// the regular ones, which a pinhole model writes as zeros.
+    pub fn to_opencv_yaml(&self) -> String {
+        let mut yaml = String::from("%YAML:1.0\n---\n");
+        writeln!(yaml, "calibration_time: \"{}\"", self.timestamp).unwrap();
+        writeln!(yaml, "image_width: {}", self.image_width).unwrap();
+        writeln!(yaml, "image_height: {}", self.image_height).unwrap();
+        if let Some(camera_index) = self.camera_index {
+            writeln!(yaml, "camera_index: {camera_index}").unwrap();
+        }
+        write_opencv_matrix(&mut yaml, "camera_matrix", 3, 3, &self.camera_matrix());
+        writeln!(yaml, "distortion_model: {}", self.ros_distortion_model()).unwrap();
+        let distortion = self.ros_distortion();
+        write_opencv_matrix(
+            &mut yaml,
+            "distortion_coefficients",
+            1,
+            distortion.len(),
+            &distortion,
+        );
+        if let Some(rms_error) = self.rms_error {
+            writeln!(yaml, "avg_reprojection_error: {rms_error}").unwrap();
+        }
+        yaml
+    }
+
+    /// Serializes the record as a ROS `sensor_msgs/CameraInfo` YAML file, as read
+    /// by `camera_info_manager`.
+    pub fn to_ros_camera_info(&self, camera_name: &str) -> serde_yaml::Result<String> {
+        let Self { fx, fy, cx, cy, .. } = *self;
+        serde_yaml::to_string(&RosCameraInfo {
+            image_width: self.image_width,
+            image_height: self.image_height,
+            camera_name: camera_name.to_string(),
+            camera_index: self.camera_index,
+            calibration_time: Some(self.timestamp.clone()),
+            camera_matrix: RosMatrix::new(3, 3, self.camera_matrix().to_vec()),
+            distortion_model: self.ros_distortion_model().to_string(),
+            distortion_coefficients: RosMatrix::new(
+                1,
+                self.ros_distortion().len(),
+                self.ros_distortion(),
+            ),
+            rectification_matrix: RosMatrix::new(
+                3,
+                3,
+                vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            ),
+            projection_matrix: RosMatrix::new(
+                3,
+                4,
+                vec![fx, 0.0, cx, 0.0, 0.0, fy, cy, 0.0, 0.0, 0.0, 1.0, 0.0],
+            ),
+        })
+    }
+
+    /// Returns the row-major camera matrix `K`.
+    fn camera_matrix(&self) -> [f64; 9] {
+        let Self {
+            fx,
+            fy,
+            cx,
+            cy,
+            skew,
+            ..
+        } = *self;
+        [fx, skew, cx, 0.0, fy, cy, 0.0, 0.0, 1.0]
+    }
+
+    /// Gets the distortion model name used by ROS.
+    fn ros_distortion_model(&self) -> &'static str {
+        match self.model {
+            CameraModelKind::Pinhole | CameraModelKind::BrownConrady => "plumb_bob",
+            CameraModelKind::KannalaBrandt => "equidistant",
+        }
+    }
+
+    /// Gets the distortion coefficients, padded to the five `plumb_bob`
+    /// coefficients for a pinhole model.
+    fn ros_distortion(&self) -> Vec<f64> {
+        match self.model {
+            CameraModelKind::Pinhole => vec![0.0; 5],
+            _ => self.distortion.clone(),
+        }
+    }
+}
+
+/// The layout of a ROS `camera_info` YAML file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosCameraInfo {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub camera_name: String,
+    /// The index of the camera, an extension ignored by the ROS parsers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_index: Option<u32>,
+    /// The time of the calibration, an extension ignored by the ROS parsers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration_time: Option<String>,
+    pub camera_matrix: RosMatrix,
+    pub distortion_model: String,
+    pub distortion_coefficients: RosMatrix,
+    pub rectification_matrix: RosMatrix,
+    pub projection_matrix: RosMatrix,
+}
+
+/// A row-major matrix in a ROS `camera_info` YAML file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosMatrix {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<f64>,
+}
+
+impl RosMatrix {
+    fn new(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        Self { rows, cols, data }
+    }
+}
+
+fn write_opencv_matrix(yaml: &mut String, name: &str, rows: usize, cols: usize, data: &[f64]) {
+    let data = data
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(yaml, "{name}: !!opencv-matrix").unwrap();
+    writeln!(yaml, "   rows: {rows}").unwrap();
+    writeln!(yaml, "   cols: {cols}").unwrap();
+    writeln!(yaml, "   dt: d").unwrap();
+    writeln!(yaml, "   data: [ {data} ]").unwrap();
+}
+
+/// Formats the current UTC time in RFC 3339 format.
+fn rfc3339_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    rfc3339(seconds)
+}
+
+/// Formats seconds since the Unix epoch as a UTC time in RFC 3339 format.
+fn rfc3339(seconds: u64) -> String {
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Convert days since the epoch to a civil date, after Howard Hinnant's algorithm
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apriltag::calibration::CameraCalibration;
+
+    fn brown_conrady() -> CameraModel {
+        CameraModel::BrownConrady {
+            intrinsics: Intrinsics {
+                fx: 800.5,
+                fy: 810.25,
+                cx: 325.0,
+                cy: 235.0,
+                skew: 0.0,
+            },
+            distortion: Distortion {
+                k1: -0.2,
+                k2: 0.05,
+                k3: 0.01,
+                p1: 0.001,
+                p2: -0.0005,
+            },
+        }
+    }
+
+    fn kannala_brandt() -> CameraModel {
+        CameraModel::KannalaBrandt {
+            intrinsics: Intrinsics {
+                fx: 400.5,
+                fy: 401.0,
+                cx: 318.0,
+                cy: 242.5,
+                skew: 0.0,
+            },
+            distortion: FisheyeDistortion {
+                k1: 0.1,
+                k2: -0.02,
+                k3: 0.003,
+                k4: -0.0004,
+            },
+        }
+    }
+
+    fn assert_same_model(calibration: &CameraCalibration, expected: &CameraModel) {
+        assert_eq!(calibration.image_width, Some(640));
+        assert_eq!(calibration.image_height, Some(480));
+        let model = CameraModel::from(calibration);
+        assert_eq!(model.kind(), expected.kind());
+        assert_eq!(model.params(), expected.params());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_709_251_199), "2024-02-29T23:59:59Z");
+        assert_eq!(rfc3339(1_709_251_200), "2024-03-01T00:00:00Z");
+        assert_eq!(rfc3339(4_107_542_400), "2100-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn distortion_in_opencv_order() {
+        let record = CalibrationRecord::new(&brown_conrady(), None, 640, 480, None);
+        assert_eq!(record.distortion, [-0.2, 0.05, 0.001, -0.0005, 0.01]);
+        let record = CalibrationRecord::new(&kannala_brandt(), None, 640, 480, None);
+        assert_eq!(record.distortion, [0.1, -0.02, 0.003, -0.0004]);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for model in [brown_conrady(), kannala_brandt()] {
+            let record = CalibrationRecord::new(&model, Some(0.3), 640, 480, Some(1));
+            let calibration = CameraCalibration::from_json(&record.to_json().unwrap()).unwrap();
+            assert_same_model(&calibration, &model);
+        }
+    }
+
+    #[test]
+    fn opencv_yaml_round_trip() {
+        for model in [brown_conrady(), kannala_brandt()] {
+            let record = CalibrationRecord::new(&model, Some(0.3), 640, 480, Some(1));
+            let calibration =
+                CameraCalibration::from_opencv_yaml(&record.to_opencv_yaml()).unwrap();
+            assert_same_model(&calibration, &model);
+        }
+    }
+
+    #[test]
+    fn ros_camera_info_round_trip() {
+        for model in [brown_conrady(), kannala_brandt()] {
+            let record = CalibrationRecord::new(&model, None, 640, 480, None);
+            let yaml = record.to_ros_camera_info("camera").unwrap();
+            let calibration = CameraCalibration::from_ros_camera_info(&yaml).unwrap();
+            assert_same_model(&calibration, &model);
+        }
+    }
+}
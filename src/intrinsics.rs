@@ -0,0 +1,290 @@
+//! Zhang-style pinhole intrinsics calibration from tag homographies.
+
+use apriltag::TagParams;
+use apriltag_nalgebra::nalgebra::{Matrix3, Matrix6, RowSVector};
+
+use crate::solver::Observation;
+
+/// Pinhole camera intrinsics in pixels.
+#[derive(Debug, Clone)]
+pub struct Intrinsics {
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub skew: f64,
+}
+
+impl Intrinsics {
+    /// Creates intrinsics with square pixels and the principal point at the image center.
+    pub fn centered(image_width: usize, image_height: usize, focal_length: f64) -> Self {
+        Self {
+            fx: focal_length,
+            fy: focal_length,
+            cx: image_width as f64 / 2.0,
+            cy: image_height as f64 / 2.0,
+            skew: 0.0,
+        }
+    }
+
+    /// Builds the pose estimation parameters for a tag of the given width.
+    ///
+    /// AprilTag's pose solver has no notion of skew, so it is dropped.
+    pub fn tag_params(&self, tag_width: f64) -> TagParams {
+        TagParams {
+            tagsize: tag_width,
+            fx: self.fx,
+            fy: self.fy,
+            cx: self.cx,
+            cy: self.cy,
+        }
+    }
+
+    /// Converts a pixel to normalized image coordinates.
+    pub fn normalize(&self, [u, v]: [f64; 2]) -> [f64; 2] {
+        let y = (v - self.cy) / self.fy;
+        let x = (u - self.cx - self.skew * y) / self.fx;
+        [x, y]
+    }
+
+    /// Converts normalized image coordinates to a pixel.
+    pub fn denormalize(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        [self.fx * x + self.skew * y + self.cx, self.fy * y + self.cy]
+    }
+
+    /// Returns the camera matrix `K`.
+    pub fn matrix(&self) -> Matrix3<f64> {
+        Matrix3::new(
+            self.fx, self.skew, self.cx, 0.0, self.fy, self.cy, 0.0, 0.0, 1.0,
+        )
+    }
+}
+
+/// Estimates the full pinhole intrinsics from the homographies of several tag
+/// views using Zhang's closed-form method.
+///
+/// Every homography maps the tag plane to the image, so the rotation columns it
+/// contains must be orthonormal. Each view contributes two linear constraints on
+/// the image of the absolute conic `B = K⁻ᵀ K⁻¹`, from which `K` is recovered.
+/// At least three views with different tag orientations are needed to estimate
+/// the skew; with only two, the skew is assumed to be zero. The tag must be
+/// tilted differently between views, since fronto-parallel views carry no
+/// information about the principal point.
+///
+/// Returns `None` if the views have different image sizes or are degenerate.
+pub fn calibrate_intrinsics(observations: &[Observation]) -> Option<Intrinsics> {
+    let first = observations.first()?;
+    let (width, height) = (first.image_width, first.image_height);
+    if observations
+        .iter()
+        .any(|observation| (observation.image_width, observation.image_height) != (width, height))
+    {
+        return None;
+    }
+    let homographies: Vec<_> = observations
+        .iter()
+        .map(|observation| Matrix3::from_row_slice(observation.detection.homography().data()))
+        .collect();
+    intrinsics_from_homographies(&homographies, width, height)
+}
+
+/// Estimates the pinhole intrinsics from plane-to-image homographies of views
+/// of the given image size with Zhang's method, as in [calibrate_intrinsics].
+///
+/// Returns `None` if there are fewer than two homographies or they are degenerate.
+pub fn intrinsics_from_homographies(
+    homographies: &[Matrix3<f64>],
+    width: usize,
+    height: usize,
+) -> Option<Intrinsics> {
+    if homographies.len() < 2 {
+        return None;
+    }
+    let assume_zero_skew = homographies.len() < 3;
+
+    // Normalize pixel coordinates around the image center for conditioning
+    let (width, height) = (width as f64, height as f64);
+    let normalization = Matrix3::new(
+        1.0 / width,
+        0.0,
+        -0.5,
+        0.0,
+        1.0 / width,
+        -0.5 * height / width,
+        0.0,
+        0.0,
+        1.0,
+    );
+
+    let mut normal = Matrix6::zeros();
+    for homography in homographies {
+        let homography = normalization * homography;
+        let homography = homography / homography.norm();
+        let v = |i: usize, j: usize| {
+            let (hi, hj) = (homography.column(i), homography.column(j));
+            RowSVector::<f64, 6>::from_row_slice(&[
+                hi[0] * hj[0],
+                hi[0] * hj[1] + hi[1] * hj[0],
+                hi[1] * hj[1],
+                hi[2] * hj[0] + hi[0] * hj[2],
+                hi[2] * hj[1] + hi[1] * hj[2],
+                hi[2] * hj[2],
+            ])
+        };
+        for row in [v(0, 1), v(0, 0) - v(1, 1)] {
+            normal += row.transpose() * row;
+        }
+    }
+    if assume_zero_skew {
+        normal[(1, 1)] += 1.0;
+    }
+
+    // The conic is the least-squares null vector of the stacked constraints
+    let eigen = normal.symmetric_eigen();
+    let b = eigen.eigenvectors.column(eigen.eigenvalues.imin());
+    let [b11, b12, b22, b13, b23, b33] = [b[0], b[1], b[2], b[3], b[4], b[5]];
+
+    let denominator = b11 * b22 - b12.powi(2);
+    let cy = (b12 * b13 - b11 * b23) / denominator;
+    let lambda = b33 - (b13.powi(2) + cy * (b12 * b13 - b11 * b23)) / b11;
+    let fx = (lambda / b11).sqrt();
+    let fy = (lambda * b11 / denominator).sqrt();
+    let skew = -b12 * fx.powi(2) * fy / lambda;
+    let cx = skew * cy / fy - b13 * fx.powi(2) / lambda;
+
+    let normalized = Intrinsics {
+        fx,
+        fy,
+        cx,
+        cy,
+        skew,
+    };
+    let camera = normalization.try_inverse()? * normalized.matrix();
+    let intrinsics = Intrinsics {
+        fx: camera[(0, 0)],
+        fy: camera[(1, 1)],
+        cx: camera[(0, 2)],
+        cy: camera[(1, 2)],
+        skew: camera[(0, 1)],
+    };
+    [
+        intrinsics.fx,
+        intrinsics.fy,
+        intrinsics.cx,
+        intrinsics.cy,
+        intrinsics.skew,
+    ]
+    .iter()
+    .all(|value| value.is_finite())
+    .then_some(intrinsics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::tag_pose;
+    use apriltag_nalgebra::nalgebra::Isometry3;
+
+    fn homography(intrinsics: &Intrinsics, pose: &Isometry3<f64>) -> Matrix3<f64> {
+        let rotation = pose.rotation.to_rotation_matrix();
+        let extrinsics = Matrix3::from_columns(&[
+            rotation.matrix().column(0).into_owned(),
+            rotation.matrix().column(1).into_owned(),
+            pose.translation.vector,
+        ]);
+        intrinsics.matrix() * extrinsics
+    }
+
+    fn tilted_views() -> Vec<Isometry3<f64>> {
+        vec![
+            tag_pose([0.4, 0.0, 0.1], [0.05, 0.02, 0.6]),
+            tag_pose([0.0, -0.5, 0.0], [-0.04, 0.03, 0.9]),
+            tag_pose([0.3, 0.3, -0.2], [0.0, -0.05, 1.1]),
+            tag_pose([-0.4, 0.2, 0.3], [0.02, 0.0, 0.7]),
+        ]
+    }
+
+    fn assert_close(actual: &Intrinsics, expected: &Intrinsics) {
+        let pairs = [
+            (actual.fx, expected.fx),
+            (actual.fy, expected.fy),
+            (actual.cx, expected.cx),
+            (actual.cy, expected.cy),
+            (actual.skew, expected.skew),
+        ];
+        for (actual_value, expected_value) in pairs {
+            assert!(
+                (actual_value - expected_value).abs() < 1e-6,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn recovers_intrinsics_with_skew() {
+        let truth = Intrinsics {
+            fx: 800.0,
+            fy: 820.0,
+            cx: 330.0,
+            cy: 235.0,
+            skew: 1.5,
+        };
+        let homographies: Vec<_> = tilted_views()
+            .iter()
+            .map(|pose| homography(&truth, pose))
+            .collect();
+        let intrinsics = intrinsics_from_homographies(&homographies, 640, 480).unwrap();
+        assert_close(&intrinsics, &truth);
+    }
+
+    #[test]
+    fn two_views_assume_zero_skew() {
+        let truth = Intrinsics {
+            fx: 800.0,
+            fy: 820.0,
+            cx: 330.0,
+            cy: 235.0,
+            skew: 0.0,
+        };
+        let homographies: Vec<_> = tilted_views()[..2]
+            .iter()
+            .map(|pose| homography(&truth, pose))
+            .collect();
+        let intrinsics = intrinsics_from_homographies(&homographies, 640, 480).unwrap();
+        assert_close(&intrinsics, &truth);
+    }
+
+    #[test]
+    fn degenerate_views() {
+        let truth = Intrinsics::centered(640, 480, 800.0);
+        let homographies: Vec<_> = tilted_views()
+            .iter()
+            .map(|pose| homography(&truth, pose))
+            .collect();
+        assert!(intrinsics_from_homographies(&homographies[..1], 640, 480).is_none());
+
+        // Views rotated only about the optical axis cannot locate the principal point
+        let fronto_parallel: Vec<_> = [
+            tag_pose([0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            tag_pose([0.0, 0.0, 0.5], [0.1, 0.0, 1.5]),
+            tag_pose([0.0, 0.0, 1.0], [0.0, 0.1, 0.8]),
+        ]
+        .iter()
+        .map(|pose| homography(&truth, pose))
+        .collect();
+        assert!(intrinsics_from_homographies(&fronto_parallel, 640, 480).is_none());
+    }
+
+    #[test]
+    fn normalize_round_trip() {
+        let intrinsics = Intrinsics {
+            fx: 800.0,
+            fy: 820.0,
+            cx: 330.0,
+            cy: 235.0,
+            skew: 1.5,
+        };
+        let [u, v] = intrinsics.denormalize(intrinsics.normalize([100.0, 400.0]));
+        assert!((u - 100.0).abs() < 1e-9 && (v - 400.0).abs() < 1e-9);
+    }
+}
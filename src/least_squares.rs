@@ -0,0 +1,121 @@
+//! Nonlinear least-squares refinement.
+
+use apriltag_nalgebra::nalgebra::{DMatrix, DVector};
+
+/// The maximum number of Levenberg-Marquardt iterations.
+const MAX_ITERATIONS: usize = 200;
+
+/// Minimizes the sum of squared residuals with the Levenberg-Marquardt method,
+/// using a forward-difference Jacobian.
+///
+/// The residual function returns `None` for parameters it cannot evaluate, such
+/// as a pose behind the camera, and such steps are rejected. The refinement
+/// stops where the Jacobian cannot be evaluated.
+/// Returns the refined parameters and their residuals, or `None` if the initial
+/// parameters cannot be evaluated.
+pub fn levenberg_marquardt<F>(
+    mut params: DVector<f64>,
+    residuals: F,
+) -> Option<(DVector<f64>, DVector<f64>)>
+where
+    F: Fn(&DVector<f64>) -> Option<DVector<f64>>,
+{
+    let mut current = residuals(&params)?;
+    let mut damping = 1e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut jacobian = DMatrix::zeros(current.len(), params.len());
+        for col in 0..params.len() {
+            let step = 1e-7 * params[col].abs().max(1.0);
+            let mut shifted = params.clone();
+            shifted[col] += step;
+            // Stop at the boundary of the region the residuals can be evaluated in
+            let Some(shifted) = residuals(&shifted) else {
+                return Some((params, current));
+            };
+            jacobian.set_column(col, &((shifted - &current) / step));
+        }
+
+        let normal = jacobian.transpose() * &jacobian;
+        let gradient = jacobian.transpose() * &current;
+        let cost = current.norm_squared();
+
+        let mut improved = false;
+        while damping < 1e12 {
+            let mut damped = normal.clone();
+            for index in 0..params.len() {
+                damped[(index, index)] += damping * normal[(index, index)].max(1e-12);
+            }
+            let Some(delta) = damped
+                .cholesky()
+                .map(|cholesky| cholesky.solve(&-&gradient))
+            else {
+                damping *= 10.0;
+                continue;
+            };
+
+            let candidate = &params + &delta;
+            match residuals(&candidate) {
+                Some(candidate_residuals) if candidate_residuals.norm_squared() < cost => {
+                    let converged = cost - candidate_residuals.norm_squared() < 1e-12 * cost;
+                    params = candidate;
+                    current = candidate_residuals;
+                    damping = (damping / 10.0).max(1e-12);
+                    improved = !converged;
+                    break;
+                }
+                _ => damping *= 10.0,
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    Some((params, current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_exponential_curve() {
+        let samples: Vec<(f64, f64)> = (0..10)
+            .map(|index| {
+                let x = index as f64 / 4.0;
+                (x, 2.0 * (-0.7 * x).exp())
+            })
+            .collect();
+        let residuals = |params: &DVector<f64>| {
+            Some(DVector::from_iterator(
+                samples.len(),
+                samples
+                    .iter()
+                    .map(|&(x, y)| params[0] * (params[1] * x).exp() - y),
+            ))
+        };
+
+        let (params, residuals) =
+            levenberg_marquardt(DVector::from_vec(vec![1.0, 0.0]), residuals).unwrap();
+        assert!((params[0] - 2.0).abs() < 1e-6, "{params}");
+        assert!((params[1] + 0.7).abs() < 1e-6, "{params}");
+        assert!(residuals.norm() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_steps_that_cannot_be_evaluated() {
+        // The minimum of (x - 2)² lies beyond the region where x < 1 is evaluable
+        let residuals = |params: &DVector<f64>| {
+            (params[0] < 1.0).then(|| DVector::from_element(1, params[0] - 2.0))
+        };
+        let (params, _) = levenberg_marquardt(DVector::from_element(1, 0.0), residuals).unwrap();
+        assert!(params[0] < 1.0);
+    }
+
+    #[test]
+    fn fails_for_unevaluable_initial_parameters() {
+        let residuals = |_: &DVector<f64>| None;
+        assert!(levenberg_marquardt(DVector::from_element(1, 0.0), residuals).is_none());
+    }
+}
@@ -1,168 +1,1047 @@
-use apriltag::{families::TagStandard41h12, DetectorBuilder, Family, Image, TagParams};
+mod averaging;
+mod board;
+mod calibration;
+mod camera_model;
+mod capture;
+mod closed_form;
+mod detector_config;
+mod distortion;
+mod export;
+mod intrinsics;
+mod least_squares;
+mod preview;
+mod solver;
+#[cfg(test)]
+mod synthetic;
+mod target;
+
+use apriltag::{
+    calibration::CameraCalibration, families::TagStandard41h12, Detection, Detector,
+    DetectorBuilder, Family, FamilyData, Image, TagSize,
+};
 use apriltag_image::{image::ImageBuffer, ImageExt};
-use nokhwa::{
-    pixel_format::LumaFormat,
-    utils::{CameraIndex, RequestedFormat, RequestedFormatType},
-    Camera,
+use apriltag_nalgebra::nalgebra::Matrix3;
+
+use anyhow::{bail, ensure, Context};
+use averaging::{average_detections, AveragedDetection};
+use board::{pixel_area, Board, GridSpec};
+use calibration::{calibrate_camera, calibrate_views, refine_pose, Calibration};
+use camera_model::{CameraModel, CameraModelKind};
+use capture::CaptureSession;
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
+use closed_form::closed_form_focal_length;
+use detector_config::DetectorConfig;
+use export::CalibrationRecord;
+use image::GrayImage;
+use intrinsics::{calibrate_intrinsics, intrinsics_from_homographies, Intrinsics};
+use preview::{run_preview, PreviewCamera};
+use solver::{apparent_distance, solve_focal_length, tag_distance, FocalEstimate, Observation};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
+use target::write_target;
 
-use clap::Parser;
+/// The number of frames a camera driver typically buffers, which are stale by
+/// the time the operator asks for a capture.
+const BUFFERED_FRAMES: usize = 4;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// First tag's distance in meters
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    selection: TagSelection,
+
+    #[command(flatten)]
+    detector: DetectorConfig,
+
+    /// A YAML config file whose `detector` section sets the detector options not given on
+    /// the command line
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+/// Selects the target tag among all tags in the scene.
+#[derive(Args)]
+struct TagSelection {
+    /// Only use the tag with this ID, ignoring all others
+    #[clap(long, global = true)]
+    tag_id: Option<usize>,
+
+    /// Detect tags of this family as name[:bits], e.g. tag36h11 or tag16h5:0, where bits is
+    /// the number of corrected bits (1 by default). The name may also be the C source file of
+    /// a custom family written by the AprilTag family generator, e.g. tagCustom.c:2. Repeat
+    /// for several families, by default tagStandard41h12 and tag36h11
+    #[clap(long = "family", global = true)]
+    families: Vec<FamilySpec>,
+}
+
+impl TagSelection {
+    /// Checks whether the detection is of the selected tag.
+    fn matches(&self, detection: &Detection) -> bool {
+        self.tag_id.is_none_or(|id| detection.id() == id)
+            && (self.families.is_empty()
+                || self
+                    .families
+                    .iter()
+                    .any(|family| detection.family_name() == family.name))
+    }
+
+    /// Creates the family with the given name, which may be a selected custom family.
+    fn family(&self, name: &str) -> anyhow::Result<Family> {
+        match self.families.iter().find(|family| family.name == name) {
+            Some(family) => family.family(),
+            None => Ok(name.parse()?),
+        }
+    }
+}
+
+/// A tag family to detect with the number of bits to correct.
+#[derive(Debug, Clone)]
+struct FamilySpec {
+    name: String,
+    /// The C source of a custom family, loaded instead of a built-in family
+    path: Option<PathBuf>,
+    bits_corrected: usize,
+}
+
+impl FamilySpec {
+    /// Creates the family, loading it again for a custom family.
+    fn family(&self) -> anyhow::Result<Family> {
+        match &self.path {
+            Some(path) => Ok(Family::custom(FamilyData::from_file(path)?)?),
+            None => Ok(self.name.parse()?),
+        }
+    }
+}
+
+impl FromStr for FamilySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, bits_corrected) = match text.rsplit_once(':') {
+            Some((name, bits)) => (
+                name,
+                bits.parse()
+                    .with_context(|| format!("invalid number of corrected bits '{bits}'"))?,
+            ),
+            None => (text, 1),
+        };
+        // The AprilTag library only builds decoding tables for up to 3 bits
+        ensure!(
+            bits_corrected <= 3,
+            "at most 3 bits can be corrected, but got {bits_corrected}"
+        );
+        // Fail early on unknown family names and invalid custom families
+        if name.ends_with(".c") {
+            let data = FamilyData::from_file(name)?;
+            let family_name = data.name.clone();
+            Family::custom(data)?;
+            return Ok(Self {
+                name: family_name,
+                path: Some(PathBuf::from(name)),
+                bits_corrected,
+            });
+        }
+        name.parse::<Family>()?;
+        Ok(Self {
+            name: name.to_string(),
+            path: None,
+            bits_corrected,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Capture one frame per distance from a camera
+    Capture {
+        #[command(flatten)]
+        target: TargetArgs,
+
+        /// The index of the camera to use as it appears to the OS
+        #[clap(short, long, default_value = "0")]
+        camera_index: u32,
+
+        /// The delay in seconds to wait before capturing the image
+        #[clap(short, long, default_value = "0", value_parser = parse_seconds)]
+        with_delay: Duration,
+
+        /// The number of frames to discard after opening the stream while exposure settles
+        #[clap(long, default_value = "30")]
+        warmup_frames: usize,
+    },
+    /// Run the estimator on saved image files without opening a camera
+    Offline {
+        #[command(flatten)]
+        target: TargetArgs,
+
+        /// The image files (PNG, JPEG or PGM), in the same order as the distances and
+        /// grouped by capture position when averaging several frames per capture
+        #[clap(required = true)]
+        images: Vec<PathBuf>,
+    },
+    /// Stream from a camera and print every detection to help position the tag
+    Preview {
+        /// The index of the camera to use as it appears to the OS
+        #[clap(short, long, default_value = "0")]
+        camera_index: u32,
+
+        /// The printed width of the tag in meters, see the estimator's --tag-width
+        #[clap(long)]
+        tag_width: f64,
+
+        /// The current focal length estimate in pixels, used to print the tag distance
+        #[clap(long, conflicts_with = "calibration")]
+        focal_length: Option<f64>,
+
+        /// A calibration (OpenCV YAML, ROS camera_info or JSON) used to print the tag distance
+        #[clap(long)]
+        calibration: Option<PathBuf>,
+
+        /// Periodically write the frame with the detections drawn on it to this image file
+        #[clap(long)]
+        overlay: Option<PathBuf>,
+
+        /// The interval in seconds between overlay image refreshes
+        #[clap(long, default_value = "1", value_parser = parse_interval)]
+        refresh: Duration,
+
+        /// The number of frames to discard after opening the stream while exposure settles
+        #[clap(long, default_value = "30")]
+        warmup_frames: usize,
+    },
+    /// Render a printable tag or grid of tags of the --family (tag36h11 by default) together
+    /// with its board description
+    Target {
+        /// The printed width of each tag in meters, in the same sense as the estimator's --tag-width
+        #[clap(long)]
+        tag_width: f64,
+
+        /// The number of tag columns
+        #[clap(long, default_value = "1")]
+        columns: usize,
+
+        /// The number of tag rows
+        #[clap(long, default_value = "1")]
+        rows: usize,
+
+        /// The ID of the top-left tag, with the IDs increasing along the rows
+        #[clap(long, default_value = "0")]
+        first_id: usize,
+
+        /// The gap between neighbouring tags in meters, by default two bits of white between
+        /// the printed tags
+        #[clap(long)]
+        spacing: Option<f64>,
+
+        /// The white margin around the tags in meters
+        #[clap(long, default_value = "0.01")]
+        margin: f64,
+
+        /// The resolution of PNG output in dots per inch
+        #[clap(long, default_value = "300")]
+        dpi: f64,
+
+        /// Write the matching board description for --board to this file
+        #[clap(long)]
+        board: Option<PathBuf>,
+
+        /// The output file, rendered as PNG, SVG or PDF depending on its extension
+        output: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct TargetArgs {
+    /// The tag's distance in meters for each capture, repeated once per capture. With a
+    /// board, the distance to the board origin
+    #[clap(long = "distance", required = true, value_parser = parse_length)]
+    distances: Vec<f64>,
+
+    /// The printed width of the tag in meters, i.e. the outer edge of its black pixels. That is
+    /// the black square of the classic families and the whole tag, data bits included, of the
+    /// standard and circle families
+    #[clap(long, required_unless_present = "board", value_parser = parse_length)]
+    tag_width: Option<f64>,
+
+    /// A board description (YAML or JSON) to calibrate from all of its tags at once instead of
+    /// a single tag
     #[clap(long)]
-    tag_distance1: f64,
+    board: Option<PathBuf>,
 
-    /// The width of the first tag in meters
+    /// The number of frames averaged at each capture position
+    #[clap(long, default_value = "1", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    frames_per_capture: usize,
+
+    /// The camera model to calibrate
+    #[clap(long, value_enum, default_value = "brown-conrady")]
+    model: CameraModelKind,
+
+    /// A previous calibration (OpenCV YAML, ROS camera_info or JSON) to compare against
     #[clap(long)]
-    tag_width: f64,
+    calibration: Option<PathBuf>,
+
+    #[command(flatten)]
+    export: ExportArgs,
+}
 
-    /// Second tag's distance in meters
+#[derive(Args)]
+struct ExportArgs {
+    /// Write the calibration to an OpenCV FileStorage YAML file
     #[clap(long)]
-    tag_distance2: f64,
+    opencv_yaml: Option<PathBuf>,
 
-    /// The index of the camera to use as it appears to the OS
-    #[clap(short, long, default_value = "0")]
-    camera_index: u32,
+    /// Write the calibration to a ROS camera_info YAML file
+    #[clap(long)]
+    ros_camera_info: Option<PathBuf>,
+
+    /// The camera name written to the ROS camera_info file
+    #[clap(long, default_value = "camera")]
+    camera_name: String,
 
-    /// The delay in seconds to wait before capturing the image
-    #[clap(short, long, default_value = "0")]
-    with_delay: f64,
+    /// Write the calibration to a JSON file
+    #[clap(long)]
+    json: Option<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
     let Cli {
-        tag_distance1,
-        tag_width,
-        tag_distance2,
-        camera_index,
-        with_delay,
+        command,
+        selection,
+        detector,
+        config,
     } = Cli::parse();
+    let detector_config = match config {
+        Some(path) => detector.or(DetectorConfig::load(&path)?),
+        None => detector,
+    };
+    let mut detector = build_detector(&selection, &detector_config)?;
+    let (target, frames, camera_index) = match command {
+        Command::Capture {
+            target,
+            camera_index,
+            with_delay,
+            warmup_frames,
+        } => {
+            let mut session = CaptureSession::open(camera_index, warmup_frames)?;
+            let frames = capture_frames(
+                &mut session,
+                with_delay,
+                &target.distances,
+                target.frames_per_capture,
+            )?;
+            (target, frames, Some(camera_index))
+        }
+        Command::Offline { target, images } => {
+            ensure!(
+                images.len() == target.distances.len() * target.frames_per_capture,
+                "got {} images but {} distances with {} frames per capture",
+                images.len(),
+                target.distances.len(),
+                target.frames_per_capture
+            );
+            let frames = images
+                .chunks(target.frames_per_capture)
+                .map(|paths| paths.iter().map(|path| load_frame(path)).collect())
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            (target, frames, None)
+        }
+        Command::Preview {
+            camera_index,
+            tag_width,
+            focal_length,
+            calibration,
+            overlay,
+            refresh,
+            warmup_frames,
+        } => {
+            let camera = match (focal_length, calibration) {
+                (Some(fx), _) => Some(PreviewCamera::FocalLength(fx)),
+                (None, Some(path)) => Some(PreviewCamera::Model(load_calibration(&path)?)),
+                (None, None) => None,
+            };
+            let mut session = CaptureSession::open(camera_index, warmup_frames)?;
+            println!("Streaming from camera {camera_index}, press Ctrl-C to stop");
+            return run_preview(
+                &mut session,
+                &mut detector,
+                &selection,
+                tag_width,
+                camera,
+                overlay.as_deref(),
+                refresh,
+            );
+        }
+        Command::Target {
+            tag_width,
+            columns,
+            rows,
+            first_id,
+            spacing,
+            margin,
+            dpi,
+            board,
+            output,
+        } => {
+            let family: Family = match selection.families.as_slice() {
+                [] => Family::tag_36h11(),
+                [family] => family.family()?,
+                _ => anyhow::bail!("a target is rendered for a single --family"),
+            };
+            let tag_size = TagSize::Printed(tag_width).border_width(&family);
+            let spacing = spacing.unwrap_or_else(|| {
+                let module = tag_size / family.width_at_border() as f64;
+                (family.total_width() - family.width_at_border() + 2) as f64 * module
+            });
+            let grid = GridSpec {
+                columns,
+                rows,
+                first_id,
+                tag_size,
+                spacing,
+            };
+            return write_target(&family, &grid, margin, dpi, &output, board.as_deref());
+        }
+    };
+    if let Some(path) = &target.board {
+        let board = Board::load(path)?;
+        return calibrate_board(
+            &board,
+            &target,
+            &frames,
+            &mut detector,
+            &selection,
+            camera_index,
+        );
+    }
+    let TargetArgs {
+        distances,
+        tag_width,
+        model,
+        calibration: calibration_file,
+        export,
+        ..
+    } = target;
+    let tag_width = tag_width.context("--tag-width is required without --board")?;
+    let loaded = calibration_file
+        .map(|path| load_calibration(&path))
+        .transpose()?;
 
-    let mut detector = DetectorBuilder::new()
-        .add_family_bits(TagStandard41h12::default(), 1)
-        .add_family_bits(Family::Tag36h11(Default::default()), 1)
-        .build()?;
-
-    let index = CameraIndex::Index(camera_index);
-    let requested =
-        RequestedFormat::new::<LumaFormat>(RequestedFormatType::AbsoluteHighestResolution);
-
-    let stdin = std::io::stdin();
-
-    // wait for enter
-    println!("Press Enter to capture the first frame");
-    let mut input = String::new();
-    stdin.read_line(&mut input)?;
-
-    // wait for delay
-    std::thread::sleep(std::time::Duration::from_secs_f64(with_delay));
-    println!("Capturing frame");
-
-    let mut camera = Camera::new(index.clone(), requested)?;
-    camera.open_stream()?;
-    let mut frame = camera.frame()?;
-    drop(camera);
-    println!("Captured frame");
-    let decoded1 = frame.decode_image::<LumaFormat>()?;
-    decoded1.save("test1.png")?;
-
-    // wait for enter
-    println!("Press Enter to capture the second frame");
-    stdin.read_line(&mut input)?;
-
-    // wait for delay
-    std::thread::sleep(std::time::Duration::from_secs_f64(with_delay));
-    println!("Capturing frame");
-
-    let mut camera = Camera::new(index, requested)?;
-    camera.open_stream()?;
-    frame = camera.frame()?;
-    drop(camera);
-    println!("Captured frame");
-    let decoded2 = frame.decode_image::<LumaFormat>()?;
-    decoded2.save("test2.png")?;
+    let mut captures = Vec::with_capacity(frames.len());
+    for (index, capture) in frames.iter().enumerate() {
+        let mut detections = Vec::with_capacity(capture.len());
+        for frame in capture {
+            let img = to_apriltag_image(frame);
+            let mut frame_detections: Vec<_> = detector
+                .detect(&img)
+                .into_iter()
+                .filter(|detection| selection.matches(detection))
+                .collect();
+            if frame_detections.len() > 1 {
+                bail!(
+                    "Multiple tags found in image {}, select one with --tag-id and --family",
+                    index + 1
+                );
+            }
+            detections.extend(frame_detections.pop());
+        }
+        ensure!(
+            detections
+                .windows(2)
+                .all(|pair| pair[0].id() == pair[1].id()
+                    && pair[0].family_name() == pair[1].family_name()),
+            "the frames of capture {} show different tags, select one with --tag-id and --family",
+            index + 1
+        );
+        // Without a calibration, a nominal focal length is enough to reproject the tag
+        let (width, height) = (capture[0].width() as usize, capture[0].height() as usize);
+        let model = loaded.clone().unwrap_or_else(|| {
+            CameraModel::Pinhole(Intrinsics::centered(width, height, width as f64))
+        });
+        let Some(averaged) = average_detections(detections, &model) else {
+            bail!("No tags found in image {}", index + 1);
+        };
+        if capture.len() > 1 {
+            println!(
+                "Capture {}: tag found in {} of {} frames, {} rejected as outliers, corner jitter {:.2}px (±{:.2}px on the mean)",
+                index + 1,
+                averaged.frames.len() + averaged.rejected,
+                capture.len(),
+                averaged.rejected,
+                averaged.corner_spread,
+                averaged.corner_standard_error()
+            );
+        }
+        captures.push((averaged, width, height));
+    }
+    // Every capture shows the same tag, so its family fixes the border width
+    let family = selection.family(&captures[0].0.detection.family_name())?;
+    ensure!(
+        captures
+            .iter()
+            .all(|(averaged, ..)| averaged.detection.family_name() == family.name()),
+        "the captures show tags of different families, select one with --family"
+    );
+    let tag_width = TagSize::Printed(tag_width).border_width(&family);
+    if family.reversed_border() {
+        println!(
+            "Border square of the {} tag: {:.4}m",
+            family.name(),
+            tag_width
+        );
+    }
 
-    // Convert to older version of image crate
-    let decoded1 =
-        ImageBuffer::from_vec(decoded1.width(), decoded1.height(), decoded1.into_raw()).unwrap();
-    let img1 = Image::from_image_buffer(&decoded1);
+    let observations: Vec<_> = captures
+        .iter()
+        .zip(&distances)
+        .map(
+            |((averaged, image_width, image_height), &distance)| Observation {
+                detection: &averaged.detection,
+                distance,
+                image_width: *image_width,
+                image_height: *image_height,
+            },
+        )
+        .collect();
 
-    // Convert to older version of image crate
-    let decoded2 =
-        ImageBuffer::from_vec(decoded2.width(), decoded2.height(), decoded2.into_raw()).unwrap();
-    let img2 = Image::from_image_buffer(&decoded2);
-
-    let mut detections1 = detector.detect(&img1);
-    let mut detections2 = detector.detect(&img2);
-    if detections1.len() > 1 {
-        println!("Multiple tags found in first image");
+    let (image_width, image_height) = (observations[0].image_width, observations[0].image_height);
+    let centered = CameraModel::Pinhole(Intrinsics::centered(image_width, image_height, 1.0));
+    let Some(estimate) = solve_focal_length(&observations, tag_width, &centered) else {
+        println!("Failed to estimate pose for any focal length");
         return Ok(());
+    };
+    println!();
+    print_focal_estimate("Focal length", &estimate, observations.len());
+    match closed_form_focal_length(&observations, tag_width) {
+        Some(closed_form) => println!(
+            "Closed-form focal length: fx {:.1}px, fy {:.1}px",
+            closed_form.fx, closed_form.fy
+        ),
+        None => println!("Failed to compute closed-form focal length"),
     }
-    if detections2.len() > 1 {
-        println!("Multiple tags found in second image");
-        return Ok(());
+    let intrinsics = calibrate_intrinsics(&observations);
+    match &intrinsics {
+        Some(intrinsics) => println!(
+            "Calibrated intrinsics: fx {:.1}px, fy {:.1}px, cx {:.1}px, cy {:.1}px, skew {:.3}",
+            intrinsics.fx, intrinsics.fy, intrinsics.cx, intrinsics.cy, intrinsics.skew
+        ),
+        None => println!(
+            "Failed to calibrate intrinsics, capture the tag at different tilts to estimate them"
+        ),
     }
-    if detections1.is_empty() {
-        println!("No tags found in first image");
-        return Ok(());
+
+    let initial = intrinsics
+        .clone()
+        .unwrap_or_else(|| Intrinsics::centered(image_width, image_height, estimate.fx));
+    let calibration = calibrate_camera(
+        &observations,
+        tag_width,
+        &CameraModel::undistorted(model, initial),
+    );
+    match &calibration {
+        Some(calibration) => {
+            print_calibration(calibration);
+            let model = &calibration.model;
+            if let Some(estimate) = solve_focal_length(&observations, tag_width, model) {
+                print_focal_estimate(
+                    "Focal length with the calibrated distortion",
+                    &estimate,
+                    observations.len(),
+                );
+            }
+        }
+        None => println!("Failed to calibrate the {model:?} model, it needs more captures"),
+    }
+
+    let solved = centered.with_focal_length(estimate.fx);
+    for (index, (observation, (averaged, ..))) in observations.iter().zip(&captures).enumerate() {
+        println!(
+            "Capture {}: measured {:.2}m",
+            index + 1,
+            observation.distance
+        );
+        match apparent_distance(observation, tag_width, &solved) {
+            Some(distance) => report("  with solved focal length", observation.distance, distance),
+            None => println!("  Failed to estimate pose {}", index + 1),
+        }
+        if let Some(spread) = distance_spread(averaged, tag_width, &solved) {
+            println!("  frame-to-frame spread: ±{spread:.3}m");
+        }
+        if let Some(distance) = intrinsics.as_ref().and_then(|intrinsics| {
+            apparent_distance(
+                observation,
+                tag_width,
+                &CameraModel::Pinhole(intrinsics.clone()),
+            )
+        }) {
+            report(
+                "  with calibrated intrinsics",
+                observation.distance,
+                distance,
+            );
+        }
+        if let Some(distance) = calibration
+            .as_ref()
+            .and_then(|calibration| apparent_distance(observation, tag_width, &calibration.model))
+        {
+            report(
+                "  with calibrated camera model",
+                observation.distance,
+                distance,
+            );
+        }
+        if let Some(distance) = loaded
+            .as_ref()
+            .and_then(|loaded| apparent_distance(observation, tag_width, loaded))
+        {
+            report("  with loaded calibration", observation.distance, distance);
+        }
     }
-    if detections2.is_empty() {
-        println!("No tags found in second image");
+
+    let record = match (&calibration, &intrinsics) {
+        (Some(calibration), _) => CalibrationRecord::new(
+            &calibration.model,
+            Some(calibration.rms_error),
+            image_width,
+            image_height,
+            camera_index,
+        ),
+        (None, Some(intrinsics)) => CalibrationRecord::new(
+            &CameraModel::Pinhole(intrinsics.clone()),
+            None,
+            image_width,
+            image_height,
+            camera_index,
+        ),
+        (None, None) => {
+            CalibrationRecord::new(&solved, None, image_width, image_height, camera_index)
+        }
+    };
+    write_exports(&record, &export)?;
+
+    Ok(())
+}
+
+/// Calibrates the camera from views of a board, using the corners of every
+/// detected board tag in every frame.
+fn calibrate_board(
+    board: &Board,
+    target: &TargetArgs,
+    frames: &[Vec<GrayImage>],
+    detector: &mut Detector,
+    selection: &TagSelection,
+    camera_index: Option<u32>,
+) -> anyhow::Result<()> {
+    let first = frames.first().and_then(|capture| capture.first());
+    let Some((image_width, image_height)) =
+        first.map(|frame| (frame.width() as usize, frame.height() as usize))
+    else {
+        println!("No images to calibrate from");
         return Ok(());
+    };
+    println!("Board with {} tags", board.len());
+
+    // The detections of the board tags in every frame, grouped by capture
+    let mut captures = Vec::with_capacity(frames.len());
+    for (index, capture) in frames.iter().enumerate() {
+        let mut capture_detections = Vec::with_capacity(capture.len());
+        for frame in capture {
+            let detections: Vec<_> = detector
+                .detect(&to_apriltag_image(frame))
+                .into_iter()
+                .filter(|detection| {
+                    selection.matches(detection) && board.tag_corners(detection.id()).is_some()
+                })
+                .collect();
+            if !detections.is_empty() {
+                capture_detections.push(detections);
+            }
+        }
+        let tags: usize = capture_detections.iter().map(Vec::len).sum();
+        if tags == 0 {
+            println!("No board tags found in image {}", index + 1);
+            return Ok(());
+        }
+        println!(
+            "Capture {}: {tags} board tag detections in {} frames",
+            index + 1,
+            capture_detections.len()
+        );
+        captures.push(capture_detections);
     }
-    loop {
-        input.clear();
-        println!("\nType a guess for focal length px");
-        stdin.read_line(&mut input)?;
-        let Ok(fx) = input.trim().parse() else {
-            eprintln!("Failed to read f64");
-            continue;
-        };
-        let detection1 = detections1.last().unwrap();
-        let Some(pose) = detection1.estimate_tag_pose(&TagParams {
-            tagsize: tag_width,
-            fx,
-            fy: fx,
-            cx: img1.width() as f64 / 2.0,
-            cy: img1.height() as f64 / 2.0,
-        }) else {
-            println!("Failed to estimate pose");
-            continue;
-        };
-        let &[x, y, z] = pose.translation().data() else {
-            unreachable!();
-        };
-        let mut apparent_distance = (x.powi(2) + y.powi(2) + z.powi(2)).sqrt();
-        println!("Apparent distance 1: {:.2}m", apparent_distance);
+
+    // One homography per frame, as the tags of a frame share their orientation
+    let homographies: Vec<_> = captures
+        .iter()
+        .flatten()
+        .filter_map(|detections| {
+            detections
+                .iter()
+                .max_by(|lhs, rhs| pixel_area(lhs).total_cmp(&pixel_area(rhs)))
+        })
+        .map(|detection| Matrix3::from_row_slice(detection.homography().data()))
+        .collect();
+    let Some(intrinsics) = intrinsics_from_homographies(&homographies, image_width, image_height)
+    else {
         println!(
-            "Error 1: {:.1}%",
-            (apparent_distance - tag_distance1).abs() / tag_distance1 * 100.0
+            "Failed to calibrate intrinsics, capture the board at different tilts to estimate them"
         );
+        return Ok(());
+    };
+    println!(
+        "Calibrated intrinsics: fx {:.1}px, fy {:.1}px, cx {:.1}px, cy {:.1}px, skew {:.3}",
+        intrinsics.fx, intrinsics.fy, intrinsics.cx, intrinsics.cy, intrinsics.skew
+    );
 
-        let detection2 = detections2.last().unwrap();
-        let Some(pose) = detection2.estimate_tag_pose(&TagParams {
-            tagsize: tag_width,
-            fx,
-            fy: fx,
-            cx: img2.width() as f64 / 2.0,
-            cy: img2.height() as f64 / 2.0,
-        }) else {
-            println!("Failed to estimate pose");
-            continue;
-        };
-        let &[x, y, z] = pose.translation().data() else {
-            unreachable!();
-        };
-        apparent_distance = (x.powi(2) + y.powi(2) + z.powi(2)).sqrt();
-        println!("Apparent distance 2: {:.2}m", apparent_distance);
+    let views: Vec<Vec<_>> = captures
+        .iter()
+        .map(|capture| {
+            capture
+                .iter()
+                .filter_map(|detections| board.view(detections, &intrinsics))
+                .collect()
+        })
+        .collect();
+    let all_views: Vec<_> = views.iter().flatten().cloned().collect();
+    let Some(calibration) = calibrate_views(
+        &all_views,
+        &CameraModel::undistorted(target.model, intrinsics),
+    ) else {
         println!(
-            "Error 2: {:.1}%",
-            (apparent_distance - tag_distance2).abs() / tag_distance2 * 100.0
+            "Failed to calibrate the {:?} model, it needs more captures",
+            target.model
         );
+        return Ok(());
+    };
+    print_calibration(&calibration);
+
+    let loaded = target
+        .calibration
+        .as_ref()
+        .map(|path| load_calibration(path))
+        .transpose()?;
+    let mut poses = calibration.target_poses.iter();
+    for (index, (capture_views, &measured)) in views.iter().zip(&target.distances).enumerate() {
+        println!("Capture {}: measured {:.2}m", index + 1, measured);
+        let distances: Vec<f64> = poses
+            .by_ref()
+            .take(capture_views.len())
+            .map(|pose| pose.translation.vector.norm())
+            .collect();
+        if let Some(distance) = mean(&distances) {
+            report("  with calibrated camera model", measured, distance);
+        }
+        if let Some(loaded) = &loaded {
+            let distances: Vec<f64> = capture_views
+                .iter()
+                .filter_map(|view| refine_pose(view, loaded))
+                .map(|pose| pose.translation.vector.norm())
+                .collect();
+            if let Some(distance) = mean(&distances) {
+                report("  with loaded calibration", measured, distance);
+            }
+        }
     }
 
+    let record = CalibrationRecord::new(
+        &calibration.model,
+        Some(calibration.rms_error),
+        image_width,
+        image_height,
+        camera_index,
+    );
+    write_exports(&record, &target.export)
+}
+
+/// Prints a focal length estimate from the given number of captures with its
+/// confidence interval.
+fn print_focal_estimate(label: &str, estimate: &FocalEstimate, captures: usize) {
+    let fx = estimate.fx;
+    match estimate.half_width {
+        Some(half_width) => println!(
+            "{label}: {fx:.1}px (95% CI {:.1}px to {:.1}px)",
+            fx - half_width,
+            fx + half_width
+        ),
+        None if captures < 2 => {
+            println!("{label}: {fx:.1}px (95% CI unavailable with fewer than two captures)")
+        }
+        None => println!("{label}: {fx:.1}px (95% CI unavailable)"),
+    }
+}
+
+/// Prints a calibrated camera model with its distortion coefficients.
+fn print_calibration(calibration: &Calibration) {
+    let Calibration {
+        model, rms_error, ..
+    } = calibration;
+    let intrinsics = model.intrinsics();
+    println!(
+        "Calibrated {:?} model: fx {:.1}px, fy {:.1}px, cx {:.1}px, cy {:.1}px (RMS reprojection error {:.2}px)",
+        model.kind(), intrinsics.fx, intrinsics.fy, intrinsics.cx, intrinsics.cy, rms_error
+    );
+    match model {
+        CameraModel::Pinhole(_) => {}
+        CameraModel::BrownConrady { distortion, .. } => println!(
+            "Distortion: k1 {:.5}, k2 {:.5}, k3 {:.5}, p1 {:.5}, p2 {:.5}",
+            distortion.k1, distortion.k2, distortion.k3, distortion.p1, distortion.p2
+        ),
+        CameraModel::KannalaBrandt { distortion, .. } => println!(
+            "Distortion: k1 {:.5}, k2 {:.5}, k3 {:.5}, k4 {:.5}",
+            distortion.k1, distortion.k2, distortion.k3, distortion.k4
+        ),
+    }
+}
+
+/// Prints an apparent distance with its residual against the measured distance.
+fn report(label: &str, measured: f64, apparent: f64) {
+    let residual = apparent - measured;
+    println!(
+        "{label}: apparent {:.2}m, residual {:+.3}m ({:+.1}%)",
+        apparent,
+        residual,
+        residual / measured * 100.0
+    );
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Computes the standard deviation of the apparent distances of the frames of
+/// an averaged capture, or `None` for fewer than two frames.
+fn distance_spread(
+    averaged: &AveragedDetection,
+    tag_width: f64,
+    model: &CameraModel,
+) -> Option<f64> {
+    let distances: Vec<f64> = averaged
+        .frames
+        .iter()
+        .filter_map(|detection| tag_distance(detection, tag_width, model))
+        .collect();
+    if distances.len() < 2 {
+        return None;
+    }
+    let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+    let variance = distances
+        .iter()
+        .map(|distance| (distance - mean).powi(2))
+        .sum::<f64>()
+        / (distances.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+/// Builds a tuned detector for the selected families, or for tagStandard41h12
+/// and tag36h11 if none is selected.
+fn build_detector(selection: &TagSelection, config: &DetectorConfig) -> anyhow::Result<Detector> {
+    let mut builder = DetectorBuilder::new();
+    if selection.families.is_empty() {
+        builder = builder
+            .add_family_bits(TagStandard41h12::default(), 1)
+            .add_family_bits(Family::Tag36h11(Default::default()), 1);
+    }
+    for family in &selection.families {
+        builder = builder.add_family_bits(family.family()?, family.bits_corrected);
+    }
+    let mut detector = builder.build()?;
+    config.apply(&mut detector)?;
+    Ok(detector)
+}
+
+/// Converts a frame to the image type of the detector.
+fn to_apriltag_image(frame: &GrayImage) -> Image {
+    // Convert to older version of image crate
+    let buffer =
+        ImageBuffer::from_vec(frame.width(), frame.height(), frame.as_raw().clone()).unwrap();
+    Image::from_image_buffer(&buffer)
+}
+
+/// Parses a positive length in meters.
+fn parse_length(text: &str) -> anyhow::Result<f64> {
+    let length: f64 = text.parse()?;
+    ensure!(
+        length.is_finite() && length > 0.0,
+        "the length must be a positive number of meters"
+    );
+    Ok(length)
+}
+
+/// Parses a non-negative number of seconds.
+fn parse_seconds(text: &str) -> anyhow::Result<Duration> {
+    let seconds: f64 = text.parse()?;
+    Ok(Duration::try_from_secs_f64(seconds)?)
+}
+
+/// Parses a positive number of seconds.
+fn parse_interval(text: &str) -> anyhow::Result<Duration> {
+    let interval = parse_seconds(text)?;
+    ensure!(
+        !interval.is_zero(),
+        "the interval must be a positive number of seconds"
+    );
+    Ok(interval)
+}
+
+/// Loads a calibration file into a camera model.
+fn load_calibration(path: &Path) -> anyhow::Result<CameraModel> {
+    let calibration = CameraCalibration::from_file(path)
+        .with_context(|| format!("failed to load calibration '{}'", path.display()))?;
+    Ok(CameraModel::from(&calibration))
+}
+
+/// Writes the calibration record to every file requested on the command line.
+fn write_exports(record: &CalibrationRecord, export: &ExportArgs) -> anyhow::Result<()> {
+    let ExportArgs {
+        opencv_yaml,
+        ros_camera_info,
+        camera_name,
+        json,
+    } = export;
+
+    if let Some(path) = opencv_yaml {
+        write_export(path, &record.to_opencv_yaml())?;
+    }
+    if let Some(path) = ros_camera_info {
+        write_export(path, &record.to_ros_camera_info(camera_name)?)?;
+    }
+    if let Some(path) = json {
+        write_export(path, &record.to_json()?)?;
+    }
     Ok(())
 }
+
+fn write_export(path: &Path, contents: &str) -> anyhow::Result<()> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("failed to write '{}'", path.display()))?;
+    println!("Wrote calibration to {}", path.display());
+    Ok(())
+}
+
+/// Captures the given number of frames per distance from the capture session,
+/// saving them to `test1.png`, `test2.png` and so on, or `test1-1.png`,
+/// `test1-2.png` and so on when capturing several frames per distance.
+fn capture_frames(
+    session: &mut CaptureSession,
+    with_delay: Duration,
+    distances: &[f64],
+    frames_per_capture: usize,
+) -> anyhow::Result<Vec<Vec<GrayImage>>> {
+    let stdin = std::io::stdin();
+    let mut input = String::new();
+    let mut captures = Vec::with_capacity(distances.len());
+
+    for (number, distance) in (1..).zip(distances) {
+        // wait for enter
+        println!("Place the tag at {distance}m and press Enter to capture frame {number}");
+        stdin.read_line(&mut input)?;
+
+        // wait for delay
+        std::thread::sleep(with_delay);
+        println!("Capturing frame");
+
+        // drop the frames the driver buffered while waiting
+        session.discard(BUFFERED_FRAMES)?;
+        let mut frames = Vec::with_capacity(frames_per_capture);
+        for frame_number in 1..=frames_per_capture {
+            let frame = session.frame()?;
+            if frames_per_capture == 1 {
+                frame.save(format!("test{number}.png"))?;
+            } else {
+                frame.save(format!("test{number}-{frame_number}.png"))?;
+            }
+            frames.push(frame);
+        }
+        println!("Captured frame");
+        captures.push(frames);
+    }
+
+    Ok(captures)
+}
+
+/// Loads a saved frame from disk as a grayscale image.
+fn load_frame(path: &Path) -> anyhow::Result<GrayImage> {
+    let image =
+        image::open(path).with_context(|| format!("failed to load image '{}'", path.display()))?;
+    Ok(image.to_luma8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::tag_detection;
+
+    fn selection(tag_id: Option<usize>, families: &[&str]) -> TagSelection {
+        TagSelection {
+            tag_id,
+            families: families.iter().map(|name| name.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_family_names() {
+        let spec: FamilySpec = "tag36h11".parse().unwrap();
+        assert_eq!(spec.name, "tag36h11");
+        assert_eq!(spec.path, None);
+        assert_eq!(spec.bits_corrected, 1);
+
+        let spec: FamilySpec = "tag16h5:0".parse().unwrap();
+        assert_eq!(spec.name, "tag16h5");
+        assert_eq!(spec.bits_corrected, 0);
+        assert_eq!(spec.family().unwrap().name(), "tag16h5");
+    }
+
+    #[test]
+    fn parses_custom_family_sources() {
+        let mut data = Family::tag_16h5().data();
+        data.name = "tagCustom16h5".to_string();
+        let path = std::env::temp_dir().join(format!("{}-tagCustom16h5.c", std::process::id()));
+        std::fs::write(&path, data.to_c_source()).unwrap();
+
+        let spec = format!("{}:2", path.display()).parse::<FamilySpec>();
+        let family = spec.as_ref().ok().map(FamilySpec::family);
+        std::fs::remove_file(&path).unwrap();
+
+        let spec = spec.unwrap();
+        assert_eq!(spec.name, "tagCustom16h5");
+        assert_eq!(spec.path.as_deref(), Some(path.as_path()));
+        assert_eq!(spec.bits_corrected, 2);
+        assert_eq!(family.unwrap().unwrap().name(), "tagCustom16h5");
+    }
+
+    #[test]
+    fn rejects_invalid_families() {
+        for text in [
+            "tagUnknown",
+            "tag36h11:many",
+            "tag36h11:-1",
+            "tag36h11:4",
+            "tag36h11:",
+            "missing.c",
+            "missing.c:1",
+        ] {
+            assert!(text.parse::<FamilySpec>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn selects_tags() {
+        let (tag0, tag1) = (tag_detection(0), tag_detection(1));
+
+        let all = selection(None, &[]);
+        assert!(all.matches(&tag0) && all.matches(&tag1));
+
+        let by_id = selection(Some(1), &[]);
+        assert!(!by_id.matches(&tag0) && by_id.matches(&tag1));
+
+        assert!(!selection(None, &["tag16h5"]).matches(&tag0));
+        assert!(selection(None, &["tag16h5", "tag36h11:2"]).matches(&tag0));
+        assert!(!selection(Some(1), &["tag36h11"]).matches(&tag0));
+        assert!(selection(Some(0), &["tag36h11"]).matches(&tag0));
+    }
+}
@@ -0,0 +1,150 @@
+//! Live preview of the detections in a camera stream.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use apriltag::{Detection, Detector, TagSize};
+use image::{GrayImage, Rgb, RgbImage};
+
+use crate::{
+    camera_model::CameraModel, capture::CaptureSession, intrinsics::Intrinsics,
+    solver::tag_distance, to_apriltag_image, TagSelection,
+};
+
+/// The color of the tag outlines in the overlay image.
+const OUTLINE_COLOR: Rgb<u8> = Rgb([0, 255, 0]);
+/// The color of the first corner and the tag center in the overlay image.
+const MARKER_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// The camera used to turn detections into distances.
+pub enum PreviewCamera {
+    /// A centered pinhole camera with the given focal length in pixels, sized
+    /// once the first frame arrives.
+    FocalLength(f64),
+    /// A fully specified camera model, e.g. loaded from a calibration file.
+    Model(CameraModel),
+}
+
+/// Streams frames from the capture session until interrupted, printing every
+/// detection and refreshing the overlay image, if any, at the given interval.
+///
+/// The tag width is the printed width, converted for each detected family.
+pub fn run_preview(
+    session: &mut CaptureSession,
+    detector: &mut Detector,
+    selection: &TagSelection,
+    tag_width: f64,
+    camera: Option<PreviewCamera>,
+    overlay: Option<&Path>,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let mut model = None;
+    let mut last_refresh: Option<Instant> = None;
+    for number in 1.. {
+        let frame = session.frame()?;
+        let model = model.get_or_insert_with(|| {
+            camera.as_ref().map(|camera| match camera {
+                PreviewCamera::FocalLength(fx) => CameraModel::Pinhole(Intrinsics::centered(
+                    frame.width() as usize,
+                    frame.height() as usize,
+                    *fx,
+                )),
+                PreviewCamera::Model(model) => model.clone(),
+            })
+        });
+
+        let image = to_apriltag_image(&frame);
+        let detections: Vec<_> = detector
+            .detect(&image)
+            .into_iter()
+            .filter(|detection| selection.matches(detection))
+            .collect();
+        print_detections(number, &detections, tag_width, model.as_ref());
+
+        if let Some(path) = overlay {
+            if last_refresh.is_none_or(|instant| instant.elapsed() >= refresh) {
+                draw_overlay(&frame, &detections).save(path)?;
+                last_refresh = Some(Instant::now());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_detections(
+    number: usize,
+    detections: &[Detection],
+    tag_width: f64,
+    model: Option<&CameraModel>,
+) {
+    if detections.is_empty() {
+        println!("Frame {number}: no tags");
+        return;
+    }
+    println!("Frame {number}: {} tag(s)", detections.len());
+    for detection in detections {
+        let corners = detection
+            .corners()
+            .map(|[x, y]| format!("({x:.1}, {y:.1})"))
+            .join(" ");
+        let distance = model
+            .and_then(|model| {
+                tag_distance(
+                    detection,
+                    TagSize::Printed(tag_width).detection_border_width(detection),
+                    model,
+                )
+            })
+            .map(|distance| format!(", distance {distance:.3}m"))
+            .unwrap_or_default();
+        println!(
+            "  {} id {}: margin {:.1}, corners {corners}{distance}",
+            detection.family_name(),
+            detection.id(),
+            detection.decision_margin()
+        );
+    }
+}
+
+/// Draws the tag outlines on a color copy of the frame.
+fn draw_overlay(frame: &GrayImage, detections: &[Detection]) -> RgbImage {
+    let mut overlay = RgbImage::from_fn(frame.width(), frame.height(), |x, y| {
+        let [value] = frame.get_pixel(x, y).0;
+        Rgb([value, value, value])
+    });
+    for detection in detections {
+        let corners = detection.corners();
+        for (index, &start) in corners.iter().enumerate() {
+            draw_line(&mut overlay, start, corners[(index + 1) % 4], OUTLINE_COLOR);
+        }
+        draw_marker(&mut overlay, corners[0], MARKER_COLOR);
+        draw_marker(&mut overlay, detection.center(), MARKER_COLOR);
+    }
+    overlay
+}
+
+fn draw_line(image: &mut RgbImage, [x0, y0]: [f64; 2], [x1, y1]: [f64; 2], color: Rgb<u8>) {
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        put_pixel(image, x0 + (x1 - x0) * t, y0 + (y1 - y0) * t, color);
+    }
+}
+
+fn draw_marker(image: &mut RgbImage, [x, y]: [f64; 2], color: Rgb<u8>) {
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            put_pixel(image, x + dx as f64, y + dy as f64, color);
+        }
+    }
+}
+
+fn put_pixel(image: &mut RgbImage, x: f64, y: f64, color: Rgb<u8>) {
+    let (x, y) = (x.round(), y.round());
+    if x >= 0.0 && y >= 0.0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
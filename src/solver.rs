@@ -0,0 +1,229 @@
+//! Focal length solver driven by the pose-estimated tag distances.
+
+use apriltag::Detection;
+
+use crate::camera_model::CameraModel;
+
+/// A tag detection together with the distance it was measured at.
+pub struct Observation<'a> {
+    pub detection: &'a Detection,
+    /// The measured distance to the tag in meters.
+    pub distance: f64,
+    pub image_width: usize,
+    pub image_height: usize,
+}
+
+/// The focal length minimizing the distance error, with its 95% confidence interval.
+#[derive(Debug, Clone)]
+pub struct FocalEstimate {
+    pub fx: f64,
+    /// The half width of the confidence interval, unavailable with fewer than
+    /// two observations.
+    pub half_width: Option<f64>,
+}
+
+/// Two-sided 97.5% quantiles of the Student's t-distribution for 1 to 30 degrees of freedom.
+const T_QUANTILES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// The number of samples used to bracket the minimum before refining it.
+const SCAN_STEPS: usize = 200;
+
+/// Computes the distance to the tag as estimated by the pose solver for the given camera model.
+pub fn apparent_distance(
+    observation: &Observation,
+    tag_width: f64,
+    model: &CameraModel,
+) -> Option<f64> {
+    tag_distance(observation.detection, tag_width, model)
+}
+
+/// Computes the distance from the camera to the center of a detected tag.
+pub fn tag_distance(detection: &Detection, tag_width: f64, model: &CameraModel) -> Option<f64> {
+    let pose = model.estimate_tag_pose(detection, tag_width)?;
+    let &[x, y, z] = pose.translation().data() else {
+        unreachable!();
+    };
+    Some((x.powi(2) + y.powi(2) + z.powi(2)).sqrt())
+}
+
+/// Computes the relative distance error of an observation for the given camera model.
+pub fn relative_error(
+    observation: &Observation,
+    tag_width: f64,
+    model: &CameraModel,
+) -> Option<f64> {
+    let distance = apparent_distance(observation, tag_width, model)?;
+    Some((distance - observation.distance) / observation.distance)
+}
+
+fn cost(observations: &[Observation], tag_width: f64, model: &CameraModel) -> f64 {
+    observations
+        .iter()
+        .map(
+            |observation| match relative_error(observation, tag_width, model) {
+                Some(error) => error.powi(2),
+                None => f64::INFINITY,
+            },
+        )
+        .sum()
+}
+
+/// Finds the focal length in pixels that minimizes the squared relative distance
+/// error across all observations.
+///
+/// Only `fx` is searched, with `fy` scaled to keep the aspect ratio of `model`,
+/// whose principal point, skew and distortion stay fixed.
+///
+/// The search scans focal lengths from a tenth to ten times the image width on a
+/// log scale, then refines the best bracket with a golden-section search.
+/// Returns `None` if no candidate yields a pose for every observation.
+pub fn solve_focal_length(
+    observations: &[Observation],
+    tag_width: f64,
+    model: &CameraModel,
+) -> Option<FocalEstimate> {
+    let width = observations
+        .iter()
+        .map(|observation| observation.image_width)
+        .max()? as f64;
+
+    let min_log = (width / 10.0).ln();
+    let max_log = (width * 10.0).ln();
+    let step = (max_log - min_log) / SCAN_STEPS as f64;
+    let log_cost = |log_fx: f64| {
+        cost(
+            observations,
+            tag_width,
+            &model.with_focal_length(log_fx.exp()),
+        )
+    };
+
+    let (best_index, best_cost) = (0..=SCAN_STEPS)
+        .map(|index| (index, log_cost(min_log + step * index as f64)))
+        .min_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))?;
+    if !best_cost.is_finite() {
+        return None;
+    }
+
+    // Golden-section search within the neighbouring samples
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let mut lower = min_log + step * best_index.saturating_sub(1) as f64;
+    let mut upper = min_log + step * (best_index + 1).min(SCAN_STEPS) as f64;
+    while upper - lower > 1e-9 {
+        let left = upper - ratio * (upper - lower);
+        let right = lower + ratio * (upper - lower);
+        if log_cost(left) < log_cost(right) {
+            upper = right;
+        } else {
+            lower = left;
+        }
+    }
+    let fx = ((lower + upper) / 2.0).exp();
+
+    Some(FocalEstimate {
+        fx,
+        half_width: confidence_half_width(observations, tag_width, model, fx),
+    })
+}
+
+/// Approximates the 95% confidence half-width of the focal length from the
+/// residual variance and the sensitivity of the residuals to the focal length.
+fn confidence_half_width(
+    observations: &[Observation],
+    tag_width: f64,
+    model: &CameraModel,
+    fx: f64,
+) -> Option<f64> {
+    let dof = observations.len().checked_sub(1).filter(|&dof| dof > 0)?;
+    let delta = fx * 1e-4;
+
+    let mut sum_squares = 0.0;
+    let mut sum_jacobian = 0.0;
+    for observation in observations {
+        let residual = relative_error(observation, tag_width, &model.with_focal_length(fx))?;
+        let forward = relative_error(observation, tag_width, &model.with_focal_length(fx + delta))?;
+        let backward =
+            relative_error(observation, tag_width, &model.with_focal_length(fx - delta))?;
+        let derivative = (forward - backward) / (2.0 * delta);
+        sum_squares += residual.powi(2);
+        sum_jacobian += derivative.powi(2);
+    }
+    if sum_jacobian == 0.0 {
+        return None;
+    }
+
+    let variance = sum_squares / dof as f64 / sum_jacobian;
+    let quantile = T_QUANTILES.get(dof - 1).copied().unwrap_or(1.96);
+    Some(quantile * variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        intrinsics::Intrinsics,
+        synthetic::{observe_tag, tag_pose, template_detection, IMAGE_HEIGHT, IMAGE_WIDTH},
+    };
+
+    const TAG_WIDTH: f64 = 0.1;
+
+    fn camera(focal_length: f64) -> CameraModel {
+        CameraModel::Pinhole(Intrinsics::centered(
+            IMAGE_WIDTH,
+            IMAGE_HEIGHT,
+            focal_length,
+        ))
+    }
+
+    #[test]
+    fn tag_distance_of_exact_projection() {
+        let model = camera(800.0);
+        let pose = tag_pose([0.2, -0.3, 0.1], [0.05, -0.02, 0.7]);
+        let detection = observe_tag(&template_detection(), &model, &pose, TAG_WIDTH);
+        let distance = tag_distance(&detection, TAG_WIDTH, &model).unwrap();
+        assert!((distance - pose.translation.vector.norm()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recovers_focal_length_from_distances() {
+        let model = camera(800.0);
+        let template = template_detection();
+        let poses = [
+            tag_pose([0.0, 0.0, 0.0], [0.0, 0.0, 0.5]),
+            tag_pose([0.3, 0.1, 0.0], [0.05, -0.03, 0.8]),
+            tag_pose([-0.2, 0.4, 0.2], [-0.1, 0.05, 1.2]),
+        ];
+        let detections: Vec<_> = poses
+            .iter()
+            .map(|pose| observe_tag(&template, &model, pose, TAG_WIDTH))
+            .collect();
+        let observations: Vec<_> = detections
+            .iter()
+            .zip(&poses)
+            .map(|(detection, pose)| Observation {
+                detection,
+                distance: pose.translation.vector.norm(),
+                image_width: IMAGE_WIDTH,
+                image_height: IMAGE_HEIGHT,
+            })
+            .collect();
+
+        let estimate = solve_focal_length(&observations, TAG_WIDTH, &camera(300.0)).unwrap();
+        assert!((estimate.fx - 800.0).abs() < 0.1, "{estimate:?}");
+        assert!(estimate.half_width.unwrap() < 0.5, "{estimate:?}");
+
+        // A single observation fits exactly and leaves no residual degrees of freedom
+        let estimate = solve_focal_length(&observations[..1], TAG_WIDTH, &camera(300.0)).unwrap();
+        assert!((estimate.fx - 800.0).abs() < 0.1, "{estimate:?}");
+        assert!(estimate.half_width.is_none());
+    }
+
+    #[test]
+    fn no_estimate_without_observations() {
+        assert!(solve_focal_length(&[], TAG_WIDTH, &camera(800.0)).is_none());
+    }
+}
@@ -0,0 +1,75 @@
+//! Synthetic tag observations for the unit tests.
+
+use apriltag::{detection::TAG_CORNERS, Detection, DetectorBuilder, Family, Image};
+use apriltag_nalgebra::nalgebra::{Isometry3, Point3, Vector3};
+
+use crate::camera_model::CameraModel;
+
+/// The width of the synthetic images in pixels.
+pub const IMAGE_WIDTH: usize = 640;
+/// The height of the synthetic images in pixels.
+pub const IMAGE_HEIGHT: usize = 480;
+
+/// Detects a rendered tag, whose corners [observe_tag] replaces.
+pub fn template_detection() -> Detection {
+    tag_detection(0)
+}
+
+/// Detects the rendered tag36h11 tag with the given ID.
+pub fn tag_detection(id: usize) -> Detection {
+    // Scale the tag up and pad it with white so that the detector finds it
+    let tag = Family::tag_36h11().tag_image(id).unwrap();
+    let (scale, padding) = (10, 20);
+    let size = tag.width() * scale + 2 * padding;
+    let mut image = Image::zeros_with_alignment(size, size, 96).unwrap();
+    for y in 0..size {
+        for x in 0..size {
+            let (tag_x, tag_y) = (
+                x.wrapping_sub(padding) / scale,
+                y.wrapping_sub(padding) / scale,
+            );
+            image[(x, y)] = if tag_x < tag.width() && tag_y < tag.height() {
+                tag[(tag_x, tag_y)]
+            } else {
+                255
+            };
+        }
+    }
+
+    let mut detector = DetectorBuilder::new()
+        .add_family_bits(Family::tag_36h11(), 1)
+        .build()
+        .unwrap();
+    detector.detect(&image).pop().unwrap()
+}
+
+/// Creates the pose of a tag rotated by the rotation vector and centered at
+/// the position in camera coordinates.
+pub fn tag_pose(rotation: [f64; 3], [x, y, z]: [f64; 3]) -> Isometry3<f64> {
+    let [rx, ry, rz] = rotation;
+    Isometry3::new(Vector3::new(x, y, z), Vector3::new(rx, ry, rz))
+}
+
+/// Projects the corners of a tag of the given width at the pose, in the order
+/// of [Detection::corners].
+pub fn project_tag(model: &CameraModel, pose: &Isometry3<f64>, tag_width: f64) -> [[f64; 2]; 4] {
+    let half_size = tag_width / 2.0;
+    TAG_CORNERS.map(|[x, y]| {
+        model
+            .project(&(pose * Point3::new(x * half_size, y * half_size, 0.0)))
+            .unwrap()
+    })
+}
+
+/// Creates a detection of a tag of the given width at the pose, as imaged by
+/// the model without noise.
+pub fn observe_tag(
+    template: &Detection,
+    model: &CameraModel,
+    pose: &Isometry3<f64>,
+    tag_width: f64,
+) -> Detection {
+    template
+        .with_corners(project_tag(model, pose, tag_width))
+        .unwrap()
+}
@@ -0,0 +1,404 @@
+//! Printable calibration targets.
+
+use std::{fmt::Write, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use apriltag::Family;
+use image::{GrayImage, Luma};
+
+use crate::board::GridSpec;
+
+/// The length of an inch in meters.
+const INCH: f64 = 0.0254;
+/// The number of PDF points per inch.
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// A black rectangle in meters from the top-left corner of the page.
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// A page of black rectangles on white paper, at physical scale.
+#[derive(Debug, Clone)]
+pub struct TargetPage {
+    /// The width of the page in meters.
+    pub width: f64,
+    /// The height of the page in meters.
+    pub height: f64,
+    rects: Vec<Rect>,
+}
+
+impl TargetPage {
+    /// Lays out the tags of a grid board with a white margin around it.
+    ///
+    /// The tag size is the width of the border square the detector locates, so
+    /// the printed tags of families with data bits outside the border are
+    /// larger than the tag size.
+    pub fn grid(family: &Family, grid: &GridSpec, margin: f64) -> anyhow::Result<Self> {
+        ensure!(
+            grid.columns > 0 && grid.rows > 0,
+            "the grid needs at least one row and column"
+        );
+        ensure!(
+            grid.tag_size.is_finite() && grid.tag_size > 0.0,
+            "the tag size must be positive"
+        );
+        ensure!(
+            margin.is_finite() && margin >= 0.0,
+            "the margin must not be negative"
+        );
+        let module = grid.tag_size / family.width_at_border() as f64;
+        let total_width = family.total_width();
+        let printed_width = total_width as f64 * module;
+        ensure!(
+            grid.tag_size + grid.spacing >= printed_width && grid.spacing.is_finite(),
+            "the tags would overlap, the spacing must be at least {:.4}m",
+            printed_width - grid.tag_size
+        );
+        let pitch = grid.tag_size + grid.spacing;
+
+        let mut rects = vec![];
+        for row in 0..grid.rows {
+            for column in 0..grid.columns {
+                let image = family.tag_image(grid.first_id + row * grid.columns + column)?;
+                let left = margin + column as f64 * pitch;
+                let top = margin + row as f64 * pitch;
+
+                // Merge the black bits of each row into runs
+                for y in 0..total_width {
+                    let mut x = 0;
+                    while x < total_width {
+                        if image[(x, y)] != 0 {
+                            x += 1;
+                            continue;
+                        }
+                        let start = x;
+                        while x < total_width && image[(x, y)] == 0 {
+                            x += 1;
+                        }
+                        rects.push(Rect {
+                            x: left + start as f64 * module,
+                            y: top + y as f64 * module,
+                            width: (x - start) as f64 * module,
+                            height: module,
+                        });
+                    }
+                }
+            }
+        }
+
+        let extent = |count: usize| (count - 1) as f64 * pitch + printed_width + 2.0 * margin;
+        Ok(Self {
+            width: extent(grid.columns),
+            height: extent(grid.rows),
+            rects,
+        })
+    }
+
+    /// Renders the page as an SVG document sized in millimeters.
+    pub fn to_svg(&self) -> String {
+        let mm = |meters: f64| meters * 1000.0;
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}mm" height="{1}mm" viewBox="0 0 {0} {1}" shape-rendering="crispEdges">"#,
+            mm(self.width),
+            mm(self.height)
+        )
+        .unwrap();
+        writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+        for rect in &self.rects {
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#,
+                mm(rect.x),
+                mm(rect.y),
+                mm(rect.width),
+                mm(rect.height)
+            )
+            .unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders the page as a single-page PDF document.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let points = |meters: f64| meters / INCH * POINTS_PER_INCH;
+        let mut content = String::from("0 g\n");
+        for rect in &self.rects {
+            // PDF coordinates start at the bottom-left corner
+            writeln!(
+                content,
+                "{:.4} {:.4} {:.4} {:.4} re f",
+                points(rect.x),
+                points(self.height - rect.y - rect.height),
+                points(rect.width),
+                points(rect.height)
+            )
+            .unwrap();
+        }
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.4} {:.4}] /Contents 4 0 R >>",
+                points(self.width),
+                points(self.height)
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{content}endstream",
+                content.len()
+            ),
+        ];
+
+        let mut pdf = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (number, object) in (1..).zip(&objects) {
+            offsets.push(pdf.len());
+            writeln!(pdf, "{number} 0 obj\n{object}\nendobj").unwrap();
+        }
+        let xref = pdf.len();
+        writeln!(pdf, "xref\n0 {}", objects.len() + 1).unwrap();
+        pdf.push_str("0000000000 65535 f \n");
+        for offset in offsets {
+            writeln!(pdf, "{offset:010} 00000 n ").unwrap();
+        }
+        writeln!(
+            pdf,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF",
+            objects.len() + 1
+        )
+        .unwrap();
+        pdf.into_bytes()
+    }
+
+    /// Rasterizes the page at the given resolution in dots per inch.
+    ///
+    /// The rectangle edges are rounded to whole pixels, so choose a resolution
+    /// at which the tag bits span a whole number of pixels for the best result.
+    pub fn to_image(&self, dpi: f64) -> GrayImage {
+        let pixels = |meters: f64| (meters / INCH * dpi).round() as u32;
+        let mut image = GrayImage::from_pixel(pixels(self.width), pixels(self.height), Luma([255]));
+        for rect in &self.rects {
+            let (left, top) = (pixels(rect.x), pixels(rect.y));
+            let right = pixels(rect.x + rect.width).min(image.width());
+            let bottom = pixels(rect.y + rect.height).min(image.height());
+            for y in top..bottom {
+                for x in left..right {
+                    image.put_pixel(x, y, Luma([0]));
+                }
+            }
+        }
+        image
+    }
+
+    /// Saves the page as PNG, SVG or PDF depending on the file extension, using
+    /// the given resolution for PNG files.
+    pub fn save(&self, path: &Path, dpi: f64) -> anyhow::Result<()> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("png") => self.to_image(dpi).save(path)?,
+            Some("svg") => std::fs::write(path, self.to_svg())?,
+            Some("pdf") => std::fs::write(path, self.to_pdf())?,
+            _ => bail!(
+                "unsupported target format '{}', use .png, .svg or .pdf",
+                path.display()
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Renders a printable target and writes the matching board description.
+pub fn write_target(
+    family: &Family,
+    grid: &GridSpec,
+    margin: f64,
+    dpi: f64,
+    output: &Path,
+    board: Option<&Path>,
+) -> anyhow::Result<()> {
+    ensure!(
+        dpi.is_finite() && dpi > 0.0,
+        "the resolution must be a positive number of dots per inch"
+    );
+    let page = TargetPage::grid(family, grid, margin)?;
+    page.save(output, dpi)
+        .with_context(|| format!("failed to write target '{}'", output.display()))?;
+    println!(
+        "Wrote {:.1}mm x {:.1}mm target to {}",
+        page.width * 1000.0,
+        page.height * 1000.0,
+        output.display()
+    );
+
+    if let Some(path) = board {
+        std::fs::write(path, grid.to_yaml()?)
+            .with_context(|| format!("failed to write board '{}'", path.display()))?;
+        println!("Wrote board description to {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A single tag36h11 tag with 1cm bits and a 1cm margin.
+    fn single_tag() -> TargetPage {
+        let grid = GridSpec {
+            columns: 1,
+            rows: 1,
+            first_id: 5,
+            tag_size: 0.08,
+            spacing: 0.02,
+        };
+        TargetPage::grid(&Family::tag_36h11(), &grid, 0.01).unwrap()
+    }
+
+    fn grid(columns: usize, rows: usize, spacing: f64) -> GridSpec {
+        GridSpec {
+            columns,
+            rows,
+            first_id: 0,
+            tag_size: 0.05,
+            spacing,
+        }
+    }
+
+    #[test]
+    fn page_size() {
+        let page = single_tag();
+        assert!((page.width - 0.12).abs() < 1e-12 && (page.height - 0.12).abs() < 1e-12);
+        let svg = page.to_svg();
+        for attribute in [" width=\"", " height=\""] {
+            let (_, value) = svg.split_once(attribute).unwrap();
+            let millimeters: f64 = value.split_once("mm").unwrap().0.parse().unwrap();
+            assert!((millimeters - 120.0).abs() < 1e-9);
+        }
+
+        // Three columns and two rows 1.5cm apart, with 6.25mm bits
+        let page = TargetPage::grid(&Family::tag_36h11(), &grid(3, 2, 0.015), 0.01).unwrap();
+        assert!((page.width - (2.0 * 0.065 + 0.0625 + 0.02)).abs() < 1e-12);
+        assert!((page.height - (0.065 + 0.0625 + 0.02)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rects_match_tag_image() {
+        let page = single_tag();
+        let image = Family::tag_36h11().tag_image(5).unwrap();
+        let bit = |meters: f64| ((meters - 0.01) / 0.01).round() as usize;
+        let mut black = vec![vec![false; 10]; 10];
+        for rect in &page.rects {
+            assert!((rect.height - 0.01).abs() < 1e-12);
+            let (x, y) = (bit(rect.x), bit(rect.y));
+            let end = bit(rect.x + rect.width);
+            // The runs are merged, so they end at a white bit or the edge
+            assert!(end == 10 || image[(end, y)] != 0);
+            for cell in &mut black[y][x..end] {
+                assert!(!*cell);
+                *cell = true;
+            }
+        }
+        for (y, row) in black.iter().enumerate() {
+            for (x, &black) in row.iter().enumerate() {
+                assert_eq!(black, image[(x, y)] == 0, "bit ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn image_at_whole_pixels_per_bit() {
+        // 25.4 dpi gives 10 pixels per 1cm bit
+        let page = single_tag();
+        let rendered = page.to_image(25.4);
+        assert_eq!(rendered.dimensions(), (120, 120));
+        let image = Family::tag_36h11().tag_image(5).unwrap();
+        for y in 0..12_usize {
+            for x in 0..12_usize {
+                let expected = match (x.checked_sub(1), y.checked_sub(1)) {
+                    (Some(x), Some(y)) if x < 10 && y < 10 => image[(x, y)],
+                    _ => 255,
+                };
+                let pixel = rendered.get_pixel(x as u32 * 10 + 5, y as u32 * 10 + 5);
+                assert_eq!(pixel[0], expected, "bit ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_cross_references() {
+        let pdf = String::from_utf8(single_tag().to_pdf()).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n") && pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/MediaBox [0 0 340.1575 340.1575]"));
+
+        let (_, startxref) = pdf.rsplit_once("startxref\n").unwrap();
+        let xref: usize = startxref.lines().next().unwrap().parse().unwrap();
+        let mut lines = pdf[xref..].lines();
+        assert_eq!(lines.next(), Some("xref"));
+        assert_eq!(lines.next(), Some("0 5"));
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        for number in 1..5 {
+            let entry = lines.next().unwrap();
+            let offset: usize = entry[..10].parse().unwrap();
+            assert_eq!(entry, format!("{offset:010} 00000 n "));
+            assert!(pdf[offset..].starts_with(&format!("{number} 0 obj\n")));
+        }
+        assert_eq!(lines.next(), Some("trailer"));
+
+        // The stream length counts the bytes between the stream keywords
+        let (head, stream) = pdf.split_once("stream\n").unwrap();
+        let length: usize = head
+            .rsplit_once("/Length ")
+            .unwrap()
+            .1
+            .split(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(stream[length..].starts_with("endstream"));
+    }
+
+    #[test]
+    fn invalid_layouts() {
+        let family = Family::tag_36h11();
+        assert!(TargetPage::grid(&family, &grid(0, 2, 0.02), 0.01).is_err());
+        assert!(TargetPage::grid(&family, &grid(2, 0, 0.02), 0.01).is_err());
+        assert!(TargetPage::grid(&family, &grid(2, 2, 0.02), -0.01).is_err());
+        assert!(TargetPage::grid(&family, &grid(2, 2, 0.02), f64::NAN).is_err());
+        // The printed tags are 1.25cm wider than the 5cm border square
+        assert!(TargetPage::grid(&family, &grid(2, 2, 0.01), 0.01).is_err());
+        let grid = GridSpec {
+            tag_size: 0.0,
+            ..grid(1, 1, 0.02)
+        };
+        assert!(TargetPage::grid(&family, &grid, 0.01).is_err());
+    }
+
+    #[test]
+    fn invalid_resolution() {
+        let output = Path::new("never-written.png");
+        for dpi in [0.0, -300.0, f64::NAN] {
+            assert!(write_target(
+                &Family::tag_36h11(),
+                &grid(1, 1, 0.02),
+                0.01,
+                dpi,
+                output,
+                None
+            )
+            .is_err());
+        }
+        assert!(!output.exists());
+    }
+}